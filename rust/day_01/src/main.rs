@@ -36,6 +36,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::identity_op)]
     fn test_total_fuel_mass() {
         assert_eq!(total_fuel_mass(2), 2 + 0);
         assert_eq!(total_fuel_mass(654), 654 + 216 + 70 + 21 + 5);
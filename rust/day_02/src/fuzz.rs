@@ -0,0 +1,470 @@
+//! Differential fuzzing of the repository's Intcode interpreters: `run_instructions`, the
+//! `intcode::Program` that day 5 runs on with each of its backends, and the C interpreter in
+//! `c/day_02/main.c`, which is built with the system C compiler (`$CC`, or `cc`).
+//!
+//! `run_instructions` now runs on `Program` too, so both are also checked against two
+//! interpreters that share no code with `intcode`: the fixed-stride one day 2 started with, and
+//! a small one written from the puzzle descriptions.
+//!
+//! Programs come from a seeded generator, so every run checks the same cases. Set `FUZZ_SEED`
+//! and `FUZZ_CASES` to try others, e.g. `FUZZ_CASES=20000 cargo test -p day_02 fuzz`. Without
+//! a C compiler the tests fail, unless `FUZZ_SKIP_C` is set to leave the C interpreter out.
+
+use crate::{run_instructions, STEP_LIMIT};
+use intcode::{Backend, Instruction, IntcodeError, OpCode, Parameter, ParameterMode, Program};
+use std::env;
+use std::fmt::Write;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::str::FromStr;
+
+const DEFAULT_SEED: u64 = 0x2019_0002;
+const DEFAULT_CASES: usize = 300;
+
+/// Day 2 programs keep their products below this so nothing overflows. C would wrap around
+/// where Rust panics.
+const PRODUCT_LIMIT: i64 = 1 << 31;
+
+type Outcome = Result<Vec<i64>, IntcodeError>;
+
+/// A xorshift64* generator. Good enough to pick instructions and addresses.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // The state must never be zero.
+        Rng(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn between(&mut self, low: i64, high: i64) -> i64 {
+        low + (self.next() % (high - low + 1) as u64) as i64
+    }
+}
+
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+fn generate(generator: fn(&mut Rng) -> Vec<i64>) -> Vec<Vec<i64>> {
+    let mut rng = Rng::new(env_or("FUZZ_SEED", DEFAULT_SEED));
+    (0..env_or("FUZZ_CASES", DEFAULT_CASES))
+        .map(|_| generator(&mut rng))
+        .collect()
+}
+
+fn position(address: usize) -> Parameter {
+    Parameter::new(address as i64, ParameterMode::Position)
+}
+
+fn immediate(value: i64) -> Parameter {
+    Parameter::new(value, ParameterMode::Immediate)
+}
+
+/// A straight run of position-mode `Add`s and `Multiply`s ending in `Halt`, which is all the C
+/// interpreter understands, followed by some data.
+///
+/// Operands are read from the data or from instructions already placed, and results are
+/// written to the data. Nothing is read out of bounds and the code never changes, so the
+/// generator can work out every value as it goes and swap a `Multiply` that would get too big
+/// for an `Add`.
+fn day_02_program(rng: &mut Rng) -> Vec<i64> {
+    let count = 1 + rng.below(12);
+    let data_start = 4 * count + 1;
+    let initial: Vec<i64> = (0..1 + rng.below(8)).map(|_| rng.between(0, 99)).collect();
+    let mut data = initial.clone();
+    let mut code = Vec::new();
+
+    for _ in 0..count {
+        let mut operand = || {
+            let address = rng.below(code.len() + data.len());
+            if address < code.len() {
+                address
+            } else {
+                data_start + address - code.len()
+            }
+        };
+        let (x, y) = (operand(), operand());
+        let value = |address: usize| match address.checked_sub(data_start) {
+            Some(offset) => data[offset],
+            None => code[address],
+        };
+        let (x_value, y_value) = (value(x), value(y));
+
+        let product = x_value
+            .checked_mul(y_value)
+            .filter(|&product| product < PRODUCT_LIMIT);
+        let (opcode, result) = match product {
+            Some(product) if rng.below(2) == 0 => (OpCode::Multiply, product),
+            _ => (OpCode::Add, x_value + y_value),
+        };
+
+        let target = rng.below(data.len());
+        data[target] = result;
+        code.extend(
+            Instruction::new(
+                opcode,
+                &[position(x), position(y), position(data_start + target)],
+            )
+            .encode(),
+        );
+    }
+
+    code.push(i64::from(OpCode::Halt));
+    code.extend(initial);
+    code
+}
+
+/// A program using every opcode except `Input` and `Output`, which `run_instructions` can't
+/// supply or collect, followed by some data.
+///
+/// Jumps only go to the start of an instruction and results are only written to the data, so
+/// the code never changes. Relative parameters can still end up at negative addresses. One
+/// operand of each `Add` and `Multiply` is a small immediate, so values grow too slowly to
+/// overflow within the step limit.
+fn full_program(rng: &mut Rng) -> Vec<i64> {
+    const OPCODES: [OpCode; 7] = [
+        OpCode::Add,
+        OpCode::Multiply,
+        OpCode::JumpIfTrue,
+        OpCode::JumpIfFalse,
+        OpCode::LessThan,
+        OpCode::Equals,
+        OpCode::AdjustRelativeBase,
+    ];
+
+    let mut opcodes: Vec<OpCode> = (0..1 + rng.below(10))
+        .map(|_| OPCODES[rng.below(OPCODES.len())])
+        .collect();
+    opcodes.push(OpCode::Halt);
+
+    let mut starts = Vec::new();
+    let mut data_start = 0;
+    for opcode in &opcodes {
+        starts.push(data_start);
+        data_start += opcode.instr_len();
+    }
+    let data_len = 1 + rng.below(8);
+    let len = data_start + data_len;
+
+    let read = |rng: &mut Rng| match rng.below(3) {
+        0 => position(rng.below(len)),
+        1 => immediate(rng.between(-9, 9)),
+        _ => Parameter::new(rng.between(-3, len as i64), ParameterMode::Relative),
+    };
+    let result = |rng: &mut Rng| position(data_start + rng.below(data_len));
+
+    let mut memory = Vec::new();
+    for opcode in opcodes {
+        let parameters = match opcode {
+            OpCode::Add | OpCode::Multiply => {
+                let bound = if opcode == OpCode::Add { 9 } else { 1 };
+                let mut operands = [read(rng), immediate(rng.between(-bound, bound))];
+                if rng.below(2) == 0 {
+                    operands.swap(0, 1);
+                }
+                vec![operands[0], operands[1], result(rng)]
+            }
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+                let target = starts[rng.below(starts.len())];
+                vec![read(rng), immediate(target as i64)]
+            }
+            OpCode::LessThan | OpCode::Equals => vec![read(rng), read(rng), result(rng)],
+            OpCode::AdjustRelativeBase => vec![immediate(rng.between(-5, 5))],
+            _ => vec![],
+        };
+        memory.extend(Instruction::new(opcode, &parameters).encode());
+    }
+
+    memory.extend((0..data_len).map(|_| rng.between(-99, 99)));
+    memory
+}
+
+fn run_day_02(program: &[i64]) -> Outcome {
+    let mut memory = program.to_vec();
+    run_instructions(&mut memory).map(|()| memory)
+}
+
+/// The interpreter day 2 started with, which steps through memory four words at a time and
+/// only knows `Add`, `Multiply` and `Halt`.
+fn run_fixed_stride(program: &[i64]) -> Outcome {
+    let mut instr_arr = program.to_vec();
+
+    for pc in (0..instr_arr.len()).step_by(4) {
+        let address = |offset: usize| instr_arr[pc + offset] as usize;
+        match instr_arr[pc] {
+            1 => {
+                let (x, y, target) = (address(1), address(2), address(3));
+                instr_arr[target] = instr_arr[x] + instr_arr[y];
+            }
+            2 => {
+                let (x, y, target) = (address(1), address(2), address(3));
+                instr_arr[target] = instr_arr[x] * instr_arr[y];
+            }
+            99 => break,
+            opcode => panic!("Unrecognized OpCode: {}", opcode),
+        }
+    }
+
+    Ok(instr_arr)
+}
+
+/// An interpreter for everything `full_program` generates, written from the puzzle
+/// descriptions. It fails the way `Program` does on negative addresses and at the step limit,
+/// and panics on anything else that could go wrong, since the generator avoids it.
+fn run_reference(program: &[i64]) -> Outcome {
+    let mut memory = program.to_vec();
+    let (mut instr_ptr, mut relative_base, mut steps) = (0, 0, 0);
+
+    loop {
+        let instruction = memory[instr_ptr];
+        let opcode = instruction % 100;
+        if opcode == 99 {
+            return Ok(memory);
+        }
+        if steps == STEP_LIMIT {
+            return Err(IntcodeError::StepLimitExceeded {
+                instr_ptr,
+                instruction,
+                limit: STEP_LIMIT,
+            });
+        }
+
+        let count = match opcode {
+            1 | 2 | 7 | 8 => 3,
+            5 | 6 => 2,
+            9 => 1,
+            _ => panic!("Unrecognized OpCode: {}", opcode),
+        };
+        // The value of each parameter, and the address it names unless it is immediate.
+        let mut values = [0; 3];
+        let mut addresses = [0; 3];
+        for i in 0..count {
+            let raw = memory[instr_ptr + 1 + i];
+            let address = match instruction / [100, 1000, 10000][i] % 10 {
+                0 => raw,
+                1 => {
+                    values[i] = raw;
+                    continue;
+                }
+                2 => relative_base + raw,
+                mode => panic!("Unrecognized parameter mode: {}", mode),
+            };
+            if address < 0 {
+                return Err(IntcodeError::NegativeAddress {
+                    instr_ptr,
+                    instruction,
+                    address,
+                });
+            }
+            addresses[i] = address as usize;
+            values[i] = memory.get(address as usize).copied().unwrap_or(0);
+        }
+
+        let mut next = instr_ptr + 1 + count;
+        let result = match opcode {
+            1 => Some(values[0] + values[1]),
+            2 => Some(values[0] * values[1]),
+            5 | 6 => {
+                if (values[0] != 0) == (opcode == 5) {
+                    next = values[1] as usize;
+                }
+                None
+            }
+            7 => Some((values[0] < values[1]) as i64),
+            8 => Some((values[0] == values[1]) as i64),
+            _ => {
+                relative_base += values[0];
+                None
+            }
+        };
+        if let Some(result) = result {
+            if addresses[2] >= memory.len() {
+                memory.resize(addresses[2] + 1, 0);
+            }
+            memory[addresses[2]] = result;
+        }
+
+        instr_ptr = next;
+        steps += 1;
+    }
+}
+
+fn run_program(program: &[i64], backend: Backend) -> Outcome {
+    let mut program = Program::new(program.to_vec())
+        .with_step_limit(STEP_LIMIT)
+        .with_backend(backend);
+    program.run().map(|()| program.memory().to_vec())
+}
+
+/// `c/day_02/main.c`, built with `fuzz_driver.c` so it can run many programs in one process.
+struct CInterpreter {
+    dir: PathBuf,
+}
+
+impl CInterpreter {
+    /// `None` if `FUZZ_SKIP_C` is set. Panics if it can't be built.
+    fn build() -> Option<Self> {
+        if env::var_os("FUZZ_SKIP_C").is_some() {
+            eprintln!("FUZZ_SKIP_C is set, leaving out the C interpreter");
+            return None;
+        }
+
+        let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+        let dir = env::temp_dir().join(format!("day_02_fuzz_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let interpreter = CInterpreter { dir };
+
+        let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/fuzz_driver.c");
+        let status = Command::new(&compiler)
+            .arg("-O1")
+            .arg(&source)
+            .arg("-o")
+            .arg(interpreter.executable())
+            .status();
+        match status {
+            Ok(status) if status.success() => Some(interpreter),
+            Ok(status) => panic!("{} failed to build {:?}: {}", compiler, source, status),
+            Err(e) => panic!(
+                "Cannot run {} to build the C interpreter: {}. Set CC to a C compiler, or set \
+                 FUZZ_SKIP_C to leave the C interpreter out",
+                compiler, e
+            ),
+        }
+    }
+
+    fn executable(&self) -> PathBuf {
+        self.dir.join("fuzz_driver")
+    }
+
+    /// The final memory of each program.
+    fn run(&self, programs: &[Vec<i64>]) -> Vec<Vec<i64>> {
+        let input = self.dir.join("programs.txt");
+        let lines: Vec<String> = programs.iter().map(|p| join(p)).collect();
+        fs::write(&input, lines.join("\n") + "\n").unwrap();
+
+        let output = Command::new(self.executable())
+            .stdin(Stdio::from(File::open(&input).unwrap()))
+            .output()
+            .expect("Could not run the C interpreter");
+        assert!(output.status.success(), "C interpreter failed");
+
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| line.split(',').map(|v| v.parse().unwrap()).collect())
+            .collect()
+    }
+}
+
+impl Drop for CInterpreter {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn join(values: &[i64]) -> String {
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Fails with every program whose outcomes aren't all `agree`.
+fn check(
+    programs: &[Vec<i64>],
+    outcomes: &[Vec<(&str, Outcome)>],
+    agree: fn(&Outcome, &Outcome) -> bool,
+) {
+    let mut report = String::new();
+    let mut divergences = 0;
+
+    for (program, outcomes) in programs.iter().zip(outcomes) {
+        let first = &outcomes[0].1;
+        if outcomes.iter().all(|(_, outcome)| agree(first, outcome)) {
+            continue;
+        }
+
+        divergences += 1;
+        writeln!(report, "\nprogram: {}", join(program)).unwrap();
+        for (name, outcome) in outcomes {
+            match outcome {
+                Ok(memory) => writeln!(report, "  {:<20} {}", name, join(memory)).unwrap(),
+                Err(e) => writeln!(report, "  {:<20} error: {}", name, e).unwrap(),
+            }
+        }
+    }
+
+    assert!(
+        divergences == 0,
+        "{} of {} programs diverged:\n{}",
+        divergences,
+        programs.len(),
+        report
+    );
+}
+
+#[test]
+fn day_02_programs_agree() {
+    let programs = generate(day_02_program);
+    let c_memories = CInterpreter::build().map(|c| c.run(&programs));
+
+    let outcomes: Vec<_> = programs
+        .iter()
+        .enumerate()
+        .map(|(i, program)| {
+            let mut outcomes = vec![
+                ("run_instructions", run_day_02(program)),
+                ("Program", run_program(program, Backend::Interpreter)),
+                (
+                    "Program (threaded)",
+                    run_program(program, Backend::Threaded),
+                ),
+                ("original day 2", run_fixed_stride(program)),
+                ("reference", run_reference(program)),
+            ];
+            if let Some(c_memories) = &c_memories {
+                outcomes.push(("C run_instructions", Ok(c_memories[i].clone())));
+            }
+            outcomes
+        })
+        .collect();
+
+    check(&programs, &outcomes, |a, b| a == b);
+}
+
+#[test]
+fn full_programs_agree() {
+    let programs = generate(full_program);
+    let outcomes: Vec<_> = programs
+        .iter()
+        .map(|program| {
+            vec![
+                ("run_instructions", run_day_02(program)),
+                ("Program", run_program(program, Backend::Interpreter)),
+                (
+                    "Program (threaded)",
+                    run_program(program, Backend::Threaded),
+                ),
+                ("reference", run_reference(program)),
+            ]
+        })
+        .collect();
+
+    check(&programs, &outcomes, |a, b| a == b);
+}
@@ -0,0 +1,38 @@
+/*
+ * Runs programs on the C day 2 interpreter for the differential fuzzing tests in fuzz.rs.
+ *
+ * Reads one comma-separated program per line from stdin, runs it with the C version of
+ * run_instructions and prints its final memory on a line of its own.
+ */
+
+#define main day_02_main
+#include "../../../c/day_02/main.c"
+#undef main
+
+int main(void) {
+	char *line = NULL;
+	size_t len = 0;
+
+	while (getline(&line, &len, stdin) != -1) {
+		unsigned long instr_arr [num_instrs(line)];
+		size_t instr_count = 0;
+		size_t i;
+
+		char *token = strtok(line, ",");
+		while (token != NULL) {
+			instr_arr[instr_count] = strtoul(token, NULL, 10);
+			token = strtok(NULL, ",");
+			++ instr_count;
+		}
+
+		run_instructions(instr_arr, instr_count);
+
+		for (i = 0; i < instr_count; ++ i) {
+			printf(i == 0 ? "%lu" : ",%lu", instr_arr[i]);
+		}
+		printf("\n");
+	}
+
+	free(line);
+	return 0;
+}
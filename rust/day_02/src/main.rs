@@ -1,28 +1,22 @@
+use intcode::{Backend, GoalSearch, IntcodeError, Program, Queue};
 use std::fs;
 
-enum OpCode {
-    Add = 1,
-    Multiply = 2,
-    Halt = 99,
-}
+#[cfg(test)]
+mod fuzz;
+mod symbolic;
 
-impl From<usize> for OpCode {
-    fn from(i: usize) -> Self {
-        match i {
-            1 => OpCode::Add,
-            2 => OpCode::Multiply,
-            99 => OpCode::Halt,
-            _ => panic!(format!("Unrecognized OpCode: {}", i)),
-        }
-    }
-}
+/// Far more steps than any noun and verb need. Combinations that run longer are assumed to
+/// never halt.
+const STEP_LIMIT: u64 = 100_000;
+
+const TARGET: i64 = 19_690_720;
 
 fn main() {
     let input = fs::read_to_string("input.txt").expect("Cannot open input file");
-    let instr_arr: Vec<usize> = input
+    let instr_arr: Vec<i64> = input
         .trim()
         .split(',')
-        .map(|i| i.parse().expect("Could not parse into i8"))
+        .map(|i| i.parse().expect("Could not parse into i64"))
         .collect();
 
     println!("Part 1: {}", part_1(instr_arr.clone()));
@@ -32,48 +26,67 @@ fn main() {
     );
 }
 
-fn part_1(mut instr_arr: Vec<usize>) -> usize {
+fn part_1(mut instr_arr: Vec<i64>) -> i64 {
     instr_arr[1] = 12;
     instr_arr[2] = 2;
 
-    run_instructions(&mut instr_arr);
+    run_instructions(&mut instr_arr).expect("Program failed");
 
     instr_arr[0]
 }
 
-fn part_2(instr_arr: Vec<usize>) -> Option<usize> {
-    for noun in 0..=99 {
-        for verb in 0..=99 {
-            let mut tmp_instr_arr = instr_arr.clone();
-            tmp_instr_arr[1] = noun;
-            tmp_instr_arr[2] = verb;
-
-            run_instructions(&mut tmp_instr_arr);
-            if tmp_instr_arr[0] == 19_690_720 {
-                return Some(100 * noun + verb);
-            }
-        }
-    }
+fn part_2(instr_arr: Vec<i64>) -> Option<i64> {
+    let polynomial = match symbolic::output_polynomial(&instr_arr) {
+        Some(polynomial) => polynomial,
+        None => return search(instr_arr),
+    };
+
+    // The polynomial is exact for every noun and verb the program runs to completion with, but
+    // a read from an address that depends on them can still fail, so each solution is checked.
+    polynomial
+        .solve(TARGET, 0..=99)
+        .into_iter()
+        .find(|&(noun, verb)| run_with(&instr_arr, noun, verb) == Some(TARGET))
+        .map(|(noun, verb)| 100 * noun + verb)
+}
 
-    None
+/// Tries every noun and verb, stopping at the first that works.
+fn search(instr_arr: Vec<i64>) -> Option<i64> {
+    let program = Program::new(instr_arr)
+        .with_backend(Backend::Threaded)
+        .with_step_limit(STEP_LIMIT);
+
+    GoalSearch::new(program, 0, TARGET)
+        .with_position(1, 0..=99)
+        .with_position(2, 0..=99)
+        .with_limit(1)
+        .run()
+        .first()
+        .map(|solution| 100 * solution[0] + solution[1])
 }
 
-fn run_instructions(instr_arr: &mut [usize]) {
-    for pc in (0..instr_arr.len()).step_by(4) {
-        match OpCode::from(instr_arr[pc]) {
-            OpCode::Add => {
-                instr_arr[instr_arr[pc + 3]] =
-                    instr_arr[instr_arr[pc + 1]] + instr_arr[instr_arr[pc + 2]];
-            }
-            OpCode::Multiply => {
-                instr_arr[instr_arr[pc + 3]] =
-                    instr_arr[instr_arr[pc + 1]] * instr_arr[instr_arr[pc + 2]];
-            }
-            OpCode::Halt => {
-                return;
-            }
-        }
-    }
+/// `instr_arr[0]` after running the program with the given noun and verb, or `None` if it
+/// fails.
+fn run_with(instr_arr: &[i64], noun: i64, verb: i64) -> Option<i64> {
+    let mut tmp_instr_arr = instr_arr.to_vec();
+    tmp_instr_arr[1] = noun;
+    tmp_instr_arr[2] = verb;
+
+    run_instructions(&mut tmp_instr_arr).ok()?;
+    Some(tmp_instr_arr[0])
+}
+
+/// Runs the program with no input, so an `Input` instruction fails instead of waiting on stdin,
+/// and discards any output.
+fn run_instructions(instr_arr: &mut Vec<i64>) -> Result<(), IntcodeError> {
+    let mut program = Program::new(std::mem::take(instr_arr))
+        .with_input(Queue::new())
+        .with_output(Queue::new())
+        .with_step_limit(STEP_LIMIT);
+    let result = program.run();
+
+    *instr_arr = program.memory().to_vec();
+    result
 }
 
 #[cfg(test)]
@@ -83,19 +96,46 @@ mod tests {
     #[test]
     fn test_run_instructions() {
         let mut instructions = vec![1, 0, 0, 0, 99];
-        run_instructions(&mut instructions);
+        run_instructions(&mut instructions).unwrap();
         assert_eq!(instructions, vec![2, 0, 0, 0, 99]);
 
         let mut instructions = vec![2, 3, 0, 3, 99];
-        run_instructions(&mut instructions);
+        run_instructions(&mut instructions).unwrap();
         assert_eq!(instructions, vec![2, 3, 0, 6, 99]);
 
         let mut instructions = vec![2, 4, 4, 5, 99, 0];
-        run_instructions(&mut instructions);
+        run_instructions(&mut instructions).unwrap();
         assert_eq!(instructions, vec![2, 4, 4, 5, 99, 9801]);
 
         let mut instructions = vec![1, 1, 1, 4, 99, 5, 6, 0, 99];
-        run_instructions(&mut instructions);
+        run_instructions(&mut instructions).unwrap();
         assert_eq!(instructions, vec![30, 1, 1, 4, 2, 5, 6, 0, 99]);
+
+        // Neither reads stdin nor prints.
+        let mut instructions = vec![4, 0, 3, 0, 99];
+        assert_eq!(
+            run_instructions(&mut instructions),
+            Err(IntcodeError::InputExhausted {
+                instr_ptr: 2,
+                instruction: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_part_2() {
+        let instr_arr: Vec<i64> = include_str!("../input.txt")
+            .trim()
+            .split(',')
+            .map(|i| i.parse().unwrap())
+            .collect();
+
+        assert!(symbolic::output_polynomial(&instr_arr).is_some());
+        assert_eq!(part_2(instr_arr.clone()), Some(4559));
+        assert_eq!(search(instr_arr), Some(4559));
+
+        // Jumps on the noun, so only a search can solve it.
+        let instr_arr = vec![1105, 0, 0, 1101, 0, 0, 0, 99];
+        assert_eq!(symbolic::output_polynomial(&instr_arr), None);
     }
 }
@@ -0,0 +1,266 @@
+//! Solves part 2 without running the program for every noun and verb.
+//!
+//! The program is run once with `instr_arr[1]` and `instr_arr[2]` left as the variables noun
+//! and verb, keeping every value as a polynomial in them. The polynomial left in
+//! `instr_arr[0]` can then be solved for the target directly. This only works while the
+//! opcodes and the addresses written to don't depend on the variables.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::ops::RangeInclusive;
+
+/// A polynomial in the noun and verb with integer coefficients.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Polynomial {
+    /// Coefficients keyed by the powers of the noun and verb in their term. Terms with a zero
+    /// coefficient are left out.
+    terms: BTreeMap<(u32, u32), i64>,
+}
+
+impl Polynomial {
+    pub fn constant(value: i64) -> Self {
+        Polynomial::term((0, 0), value)
+    }
+
+    pub fn noun() -> Self {
+        Polynomial::term((1, 0), 1)
+    }
+
+    pub fn verb() -> Self {
+        Polynomial::term((0, 1), 1)
+    }
+
+    fn term(powers: (u32, u32), coefficient: i64) -> Self {
+        let mut terms = BTreeMap::new();
+        if coefficient != 0 {
+            terms.insert(powers, coefficient);
+        }
+        Polynomial { terms }
+    }
+
+    /// The value of the polynomial if it doesn't depend on the noun or verb.
+    pub fn as_constant(&self) -> Option<i64> {
+        match self.terms.iter().next() {
+            None => Some(0),
+            Some((&(0, 0), &value)) if self.terms.len() == 1 => Some(value),
+            _ => None,
+        }
+    }
+
+    /// `None` if a coefficient overflows.
+    pub fn checked_add(&self, other: &Polynomial) -> Option<Polynomial> {
+        let mut sum = self.clone();
+        for (&powers, &coefficient) in &other.terms {
+            sum.add_term(powers, coefficient)?;
+        }
+        Some(sum)
+    }
+
+    /// `None` if a coefficient or power overflows.
+    pub fn checked_mul(&self, other: &Polynomial) -> Option<Polynomial> {
+        let mut product = Polynomial::default();
+        for (&(n1, v1), &c1) in &self.terms {
+            for (&(n2, v2), &c2) in &other.terms {
+                let powers = (n1.checked_add(n2)?, v1.checked_add(v2)?);
+                product.add_term(powers, c1.checked_mul(c2)?)?;
+            }
+        }
+        Some(product)
+    }
+
+    fn add_term(&mut self, powers: (u32, u32), coefficient: i64) -> Option<()> {
+        let sum = self
+            .terms
+            .get(&powers)
+            .copied()
+            .unwrap_or(0)
+            .checked_add(coefficient)?;
+
+        if sum == 0 {
+            self.terms.remove(&powers);
+        } else {
+            self.terms.insert(powers, sum);
+        }
+        Some(())
+    }
+
+    /// `None` if the result overflows.
+    pub fn evaluate(&self, noun: i64, verb: i64) -> Option<i64> {
+        self.terms
+            .iter()
+            .try_fold(0i64, |sum, (&(n, v), &coefficient)| {
+                let term = coefficient
+                    .checked_mul(noun.checked_pow(n)?)?
+                    .checked_mul(verb.checked_pow(v)?)?;
+                sum.checked_add(term)
+            })
+    }
+
+    /// Every noun and verb in `range` for which the polynomial equals `target`, ordered by noun
+    /// and then verb.
+    ///
+    /// Once the noun is fixed, a polynomial of degree one or less in the verb is solved
+    /// directly. Higher degrees are evaluated for each verb in turn.
+    pub fn solve(&self, target: i64, range: RangeInclusive<i64>) -> Vec<(i64, i64)> {
+        let mut solutions = Vec::new();
+
+        for noun in range.clone() {
+            match self.in_verb(noun).as_deref() {
+                Some(&[]) if target == 0 => {
+                    solutions.extend(range.clone().map(|verb| (noun, verb)));
+                }
+                Some(&[constant]) if constant == target => {
+                    solutions.extend(range.clone().map(|verb| (noun, verb)));
+                }
+                Some(&[]) | Some(&[_]) => {}
+                Some(&[constant, slope]) => {
+                    let verb = target
+                        .checked_sub(constant)
+                        .filter(|difference| difference % slope == 0)
+                        .map(|difference| difference / slope)
+                        .filter(|verb| range.contains(verb));
+                    solutions.extend(verb.map(|verb| (noun, verb)));
+                }
+                _ => solutions.extend(
+                    range
+                        .clone()
+                        .filter(|&verb| self.evaluate(noun, verb) == Some(target))
+                        .map(|verb| (noun, verb)),
+                ),
+            }
+        }
+
+        solutions
+    }
+
+    /// The coefficients of each power of the verb once the noun is fixed, lowest power first,
+    /// with trailing zeroes trimmed. `None` on overflow.
+    fn in_verb(&self, noun: i64) -> Option<Vec<i64>> {
+        let mut coefficients: Vec<i64> = Vec::new();
+        for (&(n, v), &coefficient) in &self.terms {
+            let v = v as usize;
+            if v >= coefficients.len() {
+                coefficients.resize(v + 1, 0);
+            }
+            let term = coefficient.checked_mul(noun.checked_pow(n)?)?;
+            coefficients[v] = coefficients[v].checked_add(term)?;
+        }
+
+        while coefficients.last() == Some(&0) {
+            coefficients.pop();
+        }
+        Some(coefficients)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Known(Polynomial),
+    /// Read from an address that depends on the noun or verb.
+    Unknown,
+}
+
+/// Runs the Add/Multiply/Halt program in `instr_arr` with the noun and verb as variables, and
+/// returns what `instr_arr[0]` holds when it halts.
+///
+/// Returns `None` if that can't be worked out this way: an opcode or the address of a write
+/// depends on the noun or verb, `instr_arr[0]` depends on a read from such an address, the
+/// program does anything else Add, Multiply and Halt can't, or a coefficient overflows.
+/// Reads from addresses that depend on the noun or verb are fine as long as their results
+/// don't reach `instr_arr[0]`, and the puzzle inputs rely on that: their first instruction
+/// reads from `instr_arr[noun]` and `instr_arr[verb]`.
+pub fn output_polynomial(instr_arr: &[i64]) -> Option<Polynomial> {
+    if instr_arr.len() < 3 {
+        return None;
+    }
+    let mut memory: Vec<Value> = instr_arr
+        .iter()
+        .map(|&value| Value::Known(Polynomial::constant(value)))
+        .collect();
+    memory[1] = Value::Known(Polynomial::noun());
+    memory[2] = Value::Known(Polynomial::verb());
+
+    let constant = |memory: &[Value], address: usize| match memory.get(address) {
+        Some(Value::Known(value)) => value.as_constant(),
+        _ => None,
+    };
+    let read = |memory: &[Value], address: usize| match constant(memory, address) {
+        Some(address) if address < 0 => None,
+        Some(address) => Some(
+            memory
+                .get(address as usize)
+                .cloned()
+                .unwrap_or_else(|| Value::Known(Polynomial::default())),
+        ),
+        None => Some(Value::Unknown),
+    };
+
+    let mut instr_ptr = 0;
+    loop {
+        let opcode = constant(&memory, instr_ptr)?;
+        if opcode == 99 {
+            break;
+        }
+
+        let x = read(&memory, instr_ptr + 1)?;
+        let y = read(&memory, instr_ptr + 2)?;
+        let target = usize::try_from(constant(&memory, instr_ptr + 3)?)
+            .ok()
+            .filter(|&target| target < memory.len())?;
+
+        memory[target] = match (opcode, x, y) {
+            (1, Value::Known(x), Value::Known(y)) => Value::Known(x.checked_add(&y)?),
+            (2, Value::Known(x), Value::Known(y)) => Value::Known(x.checked_mul(&y)?),
+            (1, _, _) | (2, _, _) => Value::Unknown,
+            _ => return None,
+        };
+        instr_ptr += 4;
+    }
+
+    match &memory[0] {
+        Value::Known(value) => Some(value.clone()),
+        Value::Unknown => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn polynomials() {
+        let noun = Polynomial::noun();
+        let verb = Polynomial::verb();
+        let two = Polynomial::constant(2);
+
+        // (noun + 2) * (verb + 2) - 4 = noun * verb + 2 * noun + 2 * verb
+        let p = noun
+            .checked_add(&two)
+            .unwrap()
+            .checked_mul(&verb.checked_add(&two).unwrap())
+            .unwrap()
+            .checked_add(&Polynomial::constant(-4))
+            .unwrap();
+        assert_eq!(p.evaluate(3, 5), Some(31));
+        assert_eq!(p.as_constant(), None);
+        assert_eq!(p.solve(31, 0..=99), vec![(3, 5), (5, 3)]);
+
+        let zero = p.checked_add(&p.checked_mul(&Polynomial::constant(-1)).unwrap());
+        assert_eq!(zero.unwrap().as_constant(), Some(0));
+    }
+
+    #[test]
+    fn symbolic_execution() {
+        // The first result depends on where the noun and verb point but is overwritten, then
+        // instr_arr[0] = (noun + verb) * 7.
+        let instr_arr = [1, 0, 0, 3, 1, 1, 2, 3, 2, 3, 13, 0, 99, 7];
+        let p = output_polynomial(&instr_arr).unwrap();
+        assert_eq!(p.evaluate(4, 6), Some(70));
+        assert_eq!(p.solve(14, 0..=99), vec![(0, 2), (1, 1), (2, 0)]);
+
+        // Writes noun + verb over the address the next instruction writes to.
+        assert_eq!(output_polynomial(&[1, 1, 2, 7, 1, 0, 0, 0, 99]), None);
+        // Leaves instr_arr[0] holding a value read from instr_arr[noun].
+        assert_eq!(output_polynomial(&[1, 0, 0, 0, 99]), None);
+    }
+}
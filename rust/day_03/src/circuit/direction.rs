@@ -8,7 +8,7 @@ pub enum Direction {
 
 impl From<&str> for Direction {
     fn from(input: &str) -> Self {
-        let d = input.bytes().nth(0).unwrap();
+        let d = input.bytes().next().unwrap();
         let m: i32 = input[1..].parse().unwrap();
 
         match d {
@@ -16,7 +16,7 @@ impl From<&str> for Direction {
             b'R' => Direction::Right(m),
             b'D' => Direction::Down(m),
             b'L' => Direction::Left(m),
-            _ => panic!(format!("Unknown direction {}", d)),
+            _ => panic!("Unknown direction {}", d),
         }
     }
 }
@@ -1,7 +1,5 @@
 /// Ugly solutions, but I tried to do this without converting to Strings.
-
 #[allow(clippy::unreadable_literal)]
-
 fn main() {
     println!("Part 1: {}", solution(353096..=843212));
 }
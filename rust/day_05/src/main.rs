@@ -1,11 +1,12 @@
-mod program;
-
-use crate::program::Program;
+use intcode::{IntcodeError, Program, StdinSource, StdoutSink};
+use std::convert::TryFrom;
 use std::fs;
 
-fn main() {
+fn main() -> Result<(), IntcodeError> {
     let input = fs::read_to_string("input.txt").expect("Could not read input file");
 
-    let mut program = Program::from(input.as_str());
-    program.run();
+    let mut program = Program::try_from(input.as_str())?
+        .with_input(StdinSource)
+        .with_output(StdoutSink);
+    program.run()
 }
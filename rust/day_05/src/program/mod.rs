@@ -1,8 +0,0 @@
-pub(crate) use self::instruction::Instruction;
-pub(crate) use self::instruction::OpCode;
-pub(crate) use self::instruction::Parameter;
-pub(crate) use self::instruction::ParameterMode;
-pub(crate) use self::program::Program;
-
-mod instruction;
-mod program;
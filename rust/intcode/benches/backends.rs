@@ -0,0 +1,90 @@
+//! Compares the interpreter and threaded backends on a long-running loop and on the day 2
+//! noun and verb search, which runs a short straight-line program ten thousand times.
+//!
+//! Usage: `cargo bench -p intcode --bench backends`
+
+use intcode::{assemble, Backend, Program, Queue};
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const RUNS: u32 = 5;
+
+/// Counts down from a million.
+const COUNTDOWN: &str = "
+    loop:   ADD [n], #-1 -> [n]
+            LT #0, [n] -> [t]
+            JT [t], #loop
+            OUT [n]
+            HALT
+    n:      data 1000000
+    t:      data 0
+";
+
+fn countdown(memory: &[i64], backend: Backend) {
+    let output = Queue::new();
+    let mut program = Program::new(memory.to_vec())
+        .with_backend(backend)
+        .with_output(output.clone());
+    program.run().expect("Countdown failed");
+    assert_eq!(output.drain(), vec![0]);
+}
+
+/// Runs the day 2 program once for every noun and verb, forking each run from one `Program`
+/// the way a search would.
+fn noun_verb_search(memory: &[i64], backend: Backend) {
+    let program = Program::new(memory.to_vec()).with_backend(backend);
+    let mut found = None;
+
+    for noun in 0..=99 {
+        for verb in 0..=99 {
+            let mut run = program.fork(Queue::new(), Queue::new());
+            run.write(1, noun);
+            run.write(2, verb);
+
+            if run.run().is_ok() && run.read(0) == 19_690_720 {
+                found = Some(100 * noun + verb);
+            }
+        }
+    }
+    assert!(found.is_some());
+}
+
+fn time(name: &str, backend: Backend, run: impl Fn(Backend)) -> Duration {
+    run(backend);
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        run(backend);
+    }
+    let elapsed = start.elapsed() / RUNS;
+
+    println!("{:<24} {:>10.3?} per run", name, elapsed);
+    elapsed
+}
+
+fn compare(name: &str, run: impl Fn(Backend)) {
+    let interpreter = time(
+        &format!("{} (interpreter)", name),
+        Backend::Interpreter,
+        &run,
+    );
+    let threaded = time(&format!("{} (threaded)", name), Backend::Threaded, &run);
+    println!(
+        "the threaded backend is {:.2}x as fast\n",
+        interpreter.as_secs_f64() / threaded.as_secs_f64()
+    );
+}
+
+fn main() {
+    let memory = assemble(COUNTDOWN).expect("Bad countdown");
+    compare("countdown", |backend| countdown(&memory, backend));
+
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../day_02/input.txt");
+    let input = fs::read_to_string(path).expect("Cannot open day 2 input");
+    let memory = Program::try_from(input.as_str())
+        .expect("Bad day 2 input")
+        .memory()
+        .to_vec();
+    compare("day 2 search", |backend| noun_verb_search(&memory, backend));
+}
@@ -0,0 +1,167 @@
+//! Compares paged copy-on-write memory against a flat `Vec` in a clone-heavy breadth-first
+//! search, the access pattern of searches over VM states such as the day 15 maze. The same
+//! search is also run on forked `Program`s under each backend, since that is what a real
+//! search copies.
+//!
+//! Usage: `cargo bench -p intcode --bench clone_bfs`
+
+use intcode::{Backend, Memory, Program, Queue};
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Roughly the size of a puzzle input once it has grown its working memory.
+const PROGRAM_SIZE: usize = 4096;
+const GRID_SIZE: i64 = 64;
+const RUNS: u32 = 10;
+
+/// Where the simulated program keeps its position and move counter.
+const X: usize = 1032;
+const Y: usize = 1033;
+const MOVES: usize = 1034;
+
+trait State {
+    fn load(values: Vec<i64>) -> Self;
+    fn fork(&self) -> Self;
+    fn read(&self, address: usize) -> i64;
+    fn write(&mut self, address: usize, value: i64);
+}
+
+impl State for Vec<i64> {
+    fn load(values: Vec<i64>) -> Self {
+        values
+    }
+
+    fn fork(&self) -> Self {
+        self.clone()
+    }
+
+    fn read(&self, address: usize) -> i64 {
+        self[address]
+    }
+
+    fn write(&mut self, address: usize, value: i64) {
+        self[address] = value;
+    }
+}
+
+impl State for Memory {
+    fn load(values: Vec<i64>) -> Self {
+        Memory::from(values)
+    }
+
+    fn fork(&self) -> Self {
+        self.clone()
+    }
+
+    fn read(&self, address: usize) -> i64 {
+        Memory::read(self, address)
+    }
+
+    fn write(&mut self, address: usize, value: i64) {
+        Memory::write(self, address, value)
+    }
+}
+
+impl State for Program {
+    fn load(values: Vec<i64>) -> Self {
+        Program::new(values)
+    }
+
+    fn fork(&self) -> Self {
+        Program::fork(self, Queue::new(), Queue::new())
+    }
+
+    fn read(&self, address: usize) -> i64 {
+        Program::read(self, address)
+    }
+
+    fn write(&mut self, address: usize, value: i64) {
+        Program::write(self, address, value)
+    }
+}
+
+/// A `Program` using the threaded backend.
+struct Threaded(Program);
+
+impl State for Threaded {
+    fn load(values: Vec<i64>) -> Self {
+        Threaded(Program::new(values).with_backend(Backend::Threaded))
+    }
+
+    fn fork(&self) -> Self {
+        Threaded(self.0.fork(Queue::new(), Queue::new()))
+    }
+
+    fn read(&self, address: usize) -> i64 {
+        self.0.read(address)
+    }
+
+    fn write(&mut self, address: usize, value: i64) {
+        self.0.write(address, value)
+    }
+}
+
+/// Visits every cell of the grid, cloning the parent's memory for each move the way a search
+/// over `Program`s forks at every branch. Returns the total number of moves made.
+fn bfs<S: State>() -> i64 {
+    let mut start = S::load((0..PROGRAM_SIZE as i64).collect());
+    for &address in &[X, Y, MOVES] {
+        start.write(address, 0);
+    }
+    let mut queue = VecDeque::from(vec![start]);
+    let mut seen = HashSet::new();
+    seen.insert((0, 0));
+    let mut total = 0;
+
+    while let Some(state) = queue.pop_front() {
+        let (x, y) = (state.read(X), state.read(Y));
+        total += state.read(MOVES);
+
+        for &(dx, dy) in &[(0, 1), (0, -1), (1, 0), (-1, 0)] {
+            let next = (x + dx, y + dy);
+            let in_bounds = (0..GRID_SIZE).contains(&next.0) && (0..GRID_SIZE).contains(&next.1);
+            if !in_bounds || !seen.insert(next) {
+                continue;
+            }
+
+            let mut child = state.fork();
+            child.write(X, next.0);
+            child.write(Y, next.1);
+            child.write(MOVES, state.read(MOVES) + 1);
+            queue.push_back(child);
+        }
+    }
+
+    total
+}
+
+fn time<S: State>(name: &str) -> Duration {
+    let expected = bfs::<S>();
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        assert_eq!(bfs::<S>(), expected);
+    }
+    let elapsed = start.elapsed() / RUNS;
+
+    println!("{:<22} {:>10.3?} per search", name, elapsed);
+    elapsed
+}
+
+fn main() {
+    let flat = time::<Vec<i64>>("flat Vec");
+    let paged = time::<Memory>("paged COW");
+    let interpreter = time::<Program>("Program (interpreter)");
+    let threaded = time::<Threaded>("Program (threaded)");
+
+    for (name, elapsed) in &[
+        ("paged copy-on-write", paged),
+        ("forking an interpreter Program", interpreter),
+        ("forking a threaded Program", threaded),
+    ] {
+        println!(
+            "{} is {:.1}x as fast as a flat Vec",
+            name,
+            flat.as_secs_f64() / elapsed.as_secs_f64()
+        );
+    }
+}
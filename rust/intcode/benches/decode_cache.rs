@@ -0,0 +1,180 @@
+//! Measures how much the decoded-instruction cache speeds up a long-running program, against
+//! both `Program` with the cache turned off and the original decoder, which built a `Vec` of
+//! parameters for every instruction it executed.
+//!
+//! Each time is the fastest of several runs, since anything else running on the machine only
+//! ever slows a run down.
+//!
+//! Usage: `cargo bench -p intcode --bench decode_cache`
+
+use intcode::{assemble, Memory, OpCode, Parameter, ParameterMode, Program, Queue};
+use std::convert::{TryFrom, TryInto};
+use std::time::{Duration, Instant};
+
+const LIMIT: i64 = 20_000;
+const RUNS: u32 = 20;
+
+/// Counts the primes below `LIMIT` with a sieve of Eratosthenes, addressing the sieve through
+/// the relative base.
+const SIEVE: &str = "
+            ADD #2, #0 -> [i]
+    outer:  LT [i], #LIMIT -> [t]
+            JF [t], #done
+            ADD [i], #sieve -> [target]
+            MUL [base], #-1 -> [t]
+            ADD [target], [t] -> [t]
+            ARB [t]
+            ADD [target], #0 -> [base]
+            JT [rb], #next
+            ADD [count], #1 -> [count]
+            MUL [i], [i] -> [j]
+    mark:   LT [j], #LIMIT -> [t]
+            JF [t], #next
+            ADD [j], #sieve -> [target]
+            MUL [base], #-1 -> [t]
+            ADD [target], [t] -> [t]
+            ARB [t]
+            ADD [target], #0 -> [base]
+            ADD #1, #0 -> [rb]
+            ADD [j], [i] -> [j]
+            JT #1, #mark
+    next:   ADD [i], #1 -> [i]
+            JT #1, #outer
+    done:   OUT [count]
+            HALT
+    i:      data 0
+    j:      data 0
+    t:      data 0
+    target: data 0
+    base:   data 0
+    count:  data 0
+    sieve:  data 0
+";
+
+/// Decodes the instruction at `address` the way `Instruction::decode_with` did before the
+/// cache: a fresh `Vec` of parameters, with each mode digit found using `pow`.
+fn decode_original(memory: &Memory, address: usize) -> (OpCode, Vec<Parameter>) {
+    let raw_instruction = memory.read(address);
+    let opcode = OpCode::try_from(raw_instruction % 100).expect("Bad opcode");
+    let modes = raw_instruction / 100;
+
+    let mut parameters = Vec::new();
+    for p in 0..opcode.num_params() {
+        let p_u32: u32 = p.try_into().unwrap();
+        let mode = ParameterMode::try_from((modes % 10_i64.pow(p_u32 + 1)) / 10_i64.pow(p_u32))
+            .expect("Bad parameter mode");
+        parameters.push(Parameter::new(memory.read(address + p + 1), mode));
+    }
+
+    (opcode, parameters)
+}
+
+/// Runs `memory` decoding every instruction with `decode_original`, and returns its output and
+/// the number of instructions executed. Only as much of the VM as the sieve needs is here.
+fn run_original(memory: &[i64]) -> (Vec<i64>, u64) {
+    let mut memory = Memory::from(memory.to_vec());
+    let (mut instr_ptr, mut relative_base, mut steps) = (0, 0, 0);
+    let mut output = Vec::new();
+
+    loop {
+        let (opcode, parameters) = decode_original(&memory, instr_ptr);
+        let address = |p: &Parameter| match p.mode {
+            ParameterMode::Relative => (relative_base + p.value) as usize,
+            _ => p.value as usize,
+        };
+        let value = |p: &Parameter| match p.mode {
+            ParameterMode::Immediate => p.value,
+            _ => memory.read(address(p)),
+        };
+        let mut next = instr_ptr + opcode.instr_len();
+
+        match opcode {
+            OpCode::Add | OpCode::Multiply | OpCode::LessThan | OpCode::Equals => {
+                let (x, y) = (value(&parameters[0]), value(&parameters[1]));
+                let result = match opcode {
+                    OpCode::Add => x + y,
+                    OpCode::Multiply => x * y,
+                    OpCode::LessThan => (x < y) as i64,
+                    _ => (x == y) as i64,
+                };
+                let target = address(&parameters[2]);
+                memory.write(target, result);
+            }
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+                if (value(&parameters[0]) != 0) == (opcode == OpCode::JumpIfTrue) {
+                    next = value(&parameters[1]) as usize;
+                }
+            }
+            OpCode::AdjustRelativeBase => relative_base += value(&parameters[0]),
+            OpCode::Output => output.push(value(&parameters[0])),
+            OpCode::Halt => return (output, steps),
+            OpCode::Input => panic!("The sieve takes no input"),
+        }
+
+        instr_ptr = next;
+        steps += 1;
+    }
+}
+
+fn report(name: &str, elapsed: Duration, steps: u64) {
+    println!(
+        "{:<10} {:>10.3?} per run ({:.1} M instructions/s)",
+        name,
+        elapsed,
+        steps as f64 / elapsed.as_secs_f64() / 1e6
+    );
+}
+
+fn time_original(memory: &[i64]) -> Duration {
+    let mut elapsed = Duration::MAX;
+    let mut steps = 0;
+
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let (output, run_steps) = run_original(memory);
+        elapsed = elapsed.min(start.elapsed());
+
+        assert_eq!(output, vec![2262]);
+        steps = run_steps;
+    }
+
+    report("original", elapsed, steps);
+    elapsed
+}
+
+fn time(name: &str, memory: &[i64], cached: bool) -> Duration {
+    let mut elapsed = Duration::MAX;
+    let mut steps = 0;
+
+    for _ in 0..RUNS {
+        let output = Queue::new();
+        let mut program = Program::new(memory.to_vec())
+            .with_instruction_cache(cached)
+            .with_output(output.clone());
+
+        let start = Instant::now();
+        program.run().expect("Sieve failed");
+        elapsed = elapsed.min(start.elapsed());
+
+        assert_eq!(output.drain(), vec![2262]);
+        steps = program.steps();
+    }
+
+    report(name, elapsed, steps);
+    elapsed
+}
+
+fn main() {
+    let memory = assemble(&SIEVE.replace("LIMIT", &LIMIT.to_string())).expect("Bad sieve");
+
+    let original = time_original(&memory);
+    let uncached = time("uncached", &memory, false);
+    let cached = time("cached", &memory, true);
+
+    println!(
+        "the instruction cache is {:.1}x as fast as the original decoder and {:.1}x as fast as \
+         decoding every instruction with the current one",
+        original.as_secs_f64() / cached.as_secs_f64(),
+        uncached.as_secs_f64() / cached.as_secs_f64()
+    );
+}
@@ -0,0 +1,57 @@
+; A BOOST-style self-test standing in for the day 9 puzzle input. Given input 1, it checks
+; each opcode and parameter mode the day 9 VM has to support, outputs the opcode of the
+; first check that fails and halts, or outputs a single keycode if they all pass.
+;
+; Regenerate boost.txt with `cargo run --bin asm boost.asm > boost.txt`.
+
+        ARB #2000                               ; relative base well past the program
+        IN -> [rb+1]                            ; opcode 203
+        EQ [rb+1], #1 -> [t]
+        JF [t], #fail3
+
+        MUL #4294967296, #262144 -> [rb+0]      ; 2^50
+        EQ [rb+0], #1125899906842624 -> [t]
+        JF [t], #fail2
+        ADD [rb+0], #-1125899906842623 -> [rb-5]
+        EQ [rb-5], #1 -> [t]
+        JF [t], #fail1
+
+        ARB #-1000                              ; memory written above is still there
+        EQ [rb+995], #1 -> [t]
+        JF [t], #fail9
+        LT #-3, [rb+995] -> [t]
+        JF [t], #fail7
+        LT [rb+995], #-3 -> [t]
+        JT [t], #fail7
+        EQ [rb+995], #2 -> [t]
+        JT [t], #fail8
+
+        JT [rb+995], [jt]
+        OUT #5
+        HALT
+jt_ok:  JF [rb+996], [jf]                       ; memory never written reads as 0
+        OUT #6
+        HALT
+jf_ok:  ARB #1000
+        MUL [rb+0], #3 -> [key]
+        ADD [key], [rb+1] -> [key]
+        OUT [key]
+        HALT
+
+fail1:  OUT #1
+        HALT
+fail2:  OUT #2
+        HALT
+fail3:  OUT #3
+        HALT
+fail7:  OUT #7
+        HALT
+fail8:  OUT #8
+        HALT
+fail9:  OUT #9
+        HALT
+
+t:      data 0
+key:    data 0
+jt:     data jt_ok
+jf:     data jf_ok
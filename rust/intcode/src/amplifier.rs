@@ -0,0 +1,196 @@
+use crate::{IntcodeError, Program, Queue, Status};
+
+/// A series of programs where each one's output feeds the next one's input.
+///
+/// Every amplifier runs its own copy of the same program and is first given its phase setting.
+/// The first amplifier then receives a `0` signal. With feedback enabled, the last amplifier's
+/// output loops back into the first amplifier's input.
+pub struct AmplifierChain {
+    amplifiers: Vec<Program>,
+}
+
+impl AmplifierChain {
+    pub fn new(memory: &[i64], phases: &[i64], feedback: bool) -> Self {
+        let queues: Vec<Queue> = phases.iter().map(|&p| Queue::from(vec![p])).collect();
+        let first_input = queues.first().cloned().unwrap_or_default();
+
+        let amplifiers = (0..phases.len())
+            .map(|i| {
+                let output = match queues.get(i + 1) {
+                    Some(next) => next.clone(),
+                    None if feedback => first_input.clone(),
+                    None => Queue::new(),
+                };
+                Program::new(memory.to_vec())
+                    .with_input(queues[i].clone())
+                    .with_output(output)
+            })
+            .collect();
+
+        first_input.push(0);
+
+        AmplifierChain { amplifiers }
+    }
+
+    /// Runs every amplifier until all of them halt and returns the last signal produced by the
+    /// final amplifier.
+    ///
+    /// Amplifiers take turns, each running until it halts or blocks on input. If a full round
+    /// passes without any amplifier executing an instruction, they are deadlocked and the first
+    /// one still waiting for input is reported as having run out.
+    pub fn run(&mut self) -> Result<Option<i64>, IntcodeError> {
+        let last = self.amplifiers.len().saturating_sub(1);
+        let mut signal = None;
+        let mut halted = vec![false; self.amplifiers.len()];
+
+        while halted.iter().any(|&h| !h) {
+            let steps_before: u64 = self.amplifiers.iter().map(|a| a.steps()).sum();
+
+            for (i, amplifier) in self.amplifiers.iter_mut().enumerate() {
+                while !halted[i] {
+                    match amplifier.resume()? {
+                        Status::Output(value) if i == last => signal = Some(value),
+                        Status::Output(_) => {}
+                        Status::NeedsInput => break,
+                        Status::Halted => halted[i] = true,
+                    }
+                }
+            }
+
+            // Programs that start on a `Halt` halt without executing anything.
+            let stuck = match halted.iter().position(|&h| !h) {
+                Some(stuck) => stuck,
+                None => break,
+            };
+            let steps_after: u64 = self.amplifiers.iter().map(|a| a.steps()).sum();
+            if steps_before == steps_after {
+                let stuck = &self.amplifiers[stuck];
+                return Err(IntcodeError::InputExhausted {
+                    instr_ptr: stuck.instr_ptr(),
+                    instruction: stuck.read(stuck.instr_ptr()),
+                });
+            }
+        }
+
+        Ok(signal)
+    }
+}
+
+/// Tries every ordering of `phases` and returns the highest thruster signal along with the
+/// phase settings that produced it.
+pub fn max_thruster_signal(
+    memory: &[i64],
+    phases: &[i64],
+    feedback: bool,
+) -> Result<Option<(i64, Vec<i64>)>, IntcodeError> {
+    let mut best: Option<(i64, Vec<i64>)> = None;
+
+    for permutation in permutations(phases) {
+        let signal = AmplifierChain::new(memory, &permutation, feedback).run()?;
+
+        if let Some(signal) = signal {
+            if best.as_ref().is_none_or(|(b, _)| signal > *b) {
+                best = Some((signal, permutation));
+            }
+        }
+    }
+
+    Ok(best)
+}
+
+/// Every ordering of `values`, generated with Heap's algorithm.
+fn permutations(values: &[i64]) -> Vec<Vec<i64>> {
+    let mut values = values.to_vec();
+    let mut result = vec![values.clone()];
+    let mut counters = vec![0; values.len()];
+    let mut i = 0;
+
+    while i < values.len() {
+        if counters[i] < i {
+            if i % 2 == 0 {
+                values.swap(0, i);
+            } else {
+                values.swap(counters[i], i);
+            }
+            result.push(values.clone());
+            counters[i] += 1;
+            i = 0;
+        } else {
+            counters[i] = 0;
+            i += 1;
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(source: &str) -> Vec<i64> {
+        source.split(',').map(|s| s.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn permutation_count() {
+        let mut perms = permutations(&[0, 1, 2, 3]);
+        assert_eq!(perms.len(), 24);
+
+        perms.sort();
+        perms.dedup();
+        assert_eq!(perms.len(), 24);
+    }
+
+    #[test]
+    fn serial_chain() {
+        let memory = parse("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0");
+        assert_eq!(
+            AmplifierChain::new(&memory, &[4, 3, 2, 1, 0], false).run(),
+            Ok(Some(43210))
+        );
+
+        let memory =
+            parse("3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0");
+        assert_eq!(
+            max_thruster_signal(&memory, &[0, 1, 2, 3, 4], false),
+            Ok(Some((54321, vec![0, 1, 2, 3, 4])))
+        );
+    }
+
+    #[test]
+    fn feedback_loop() {
+        let memory = parse(
+            "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,\
+             0,5",
+        );
+        assert_eq!(
+            max_thruster_signal(&memory, &[5, 6, 7, 8, 9], true),
+            Ok(Some((139_629_729, vec![9, 8, 7, 6, 5])))
+        );
+
+        let memory = parse(
+            "3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,\
+             1,53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,\
+             0,0,10",
+        );
+        assert_eq!(
+            max_thruster_signal(&memory, &[5, 6, 7, 8, 9], true),
+            Ok(Some((18216, vec![9, 7, 8, 5, 6])))
+        );
+    }
+
+    #[test]
+    fn deadlock() {
+        // Each amplifier wants two inputs after its phase but only ever gets one.
+        let memory = parse("3,0,3,0,3,0,4,0,99");
+
+        assert!(AmplifierChain::new(&memory, &[1, 2], false).run().is_err());
+    }
+
+    #[test]
+    fn halts_without_running() {
+        assert_eq!(AmplifierChain::new(&[99], &[0, 1], false).run(), Ok(None));
+        assert_eq!(AmplifierChain::new(&[99], &[0, 1], true).run(), Ok(None));
+    }
+}
@@ -0,0 +1,157 @@
+use crate::{IntcodeError, Program, Queue, Status};
+use std::convert::TryFrom;
+
+/// Something an ASCII program produced.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AsciiOutput {
+    /// A line of text, without its newline. Text that isn't followed by a newline is returned
+    /// as a line once the program stops producing it.
+    Line(String),
+    /// A value outside the ASCII range, such as a puzzle answer.
+    Value(i64),
+    /// The program is waiting for more input.
+    NeedsInput,
+    /// The program halted.
+    Halted,
+}
+
+/// Runs a `Program` that talks in ASCII: input is sent as character codes and output is read
+/// back as lines of text.
+pub struct AsciiProgram {
+    program: Program,
+    input: Queue,
+    line: String,
+    /// A value that ended a partial line, returned after that line.
+    pending: Option<i64>,
+}
+
+impl AsciiProgram {
+    /// Takes over the program's input and output.
+    pub fn new(program: Program) -> Self {
+        let input = Queue::new();
+        let program = program.with_input(input.clone()).with_output(|_: i64| {});
+
+        AsciiProgram {
+            program,
+            input,
+            line: String::new(),
+            pending: None,
+        }
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// Queues the character codes of `text` as input. Nothing is added to the end.
+    pub fn send(&mut self, text: &str) {
+        for c in text.chars() {
+            self.input.push(c as i64);
+        }
+    }
+
+    /// Queues `line` followed by a newline.
+    pub fn send_line(&mut self, line: &str) {
+        self.send(line);
+        self.input.push(i64::from(b'\n'));
+    }
+
+    /// Runs until the program finishes a line, outputs a non-ASCII value, needs input or halts.
+    pub fn next_output(&mut self) -> Result<AsciiOutput, IntcodeError> {
+        if let Some(value) = self.pending.take() {
+            return Ok(AsciiOutput::Value(value));
+        }
+
+        loop {
+            let status = self.program.resume()?;
+            if let Status::Output(value) = status {
+                match u8::try_from(value) {
+                    Ok(b'\n') => return Ok(AsciiOutput::Line(self.take_line())),
+                    Ok(byte) if byte.is_ascii() => self.line.push(char::from(byte)),
+                    _ if self.line.is_empty() => return Ok(AsciiOutput::Value(value)),
+                    _ => {
+                        self.pending = Some(value);
+                        return Ok(AsciiOutput::Line(self.take_line()));
+                    }
+                }
+                continue;
+            }
+
+            if !self.line.is_empty() {
+                return Ok(AsciiOutput::Line(self.take_line()));
+            }
+            return Ok(match status {
+                Status::NeedsInput => AsciiOutput::NeedsInput,
+                _ => AsciiOutput::Halted,
+            });
+        }
+    }
+
+    /// Everything the program outputs until it needs input or halts. The last entry says which.
+    pub fn read_until_blocked(&mut self) -> Result<Vec<AsciiOutput>, IntcodeError> {
+        let mut outputs = Vec::new();
+        loop {
+            let output = self.next_output()?;
+            let blocked = output == AsciiOutput::NeedsInput || output == AsciiOutput::Halted;
+            outputs.push(output);
+            if blocked {
+                return Ok(outputs);
+            }
+        }
+    }
+
+    fn take_line(&mut self) -> String {
+        std::mem::take(&mut self.line)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::assemble;
+
+    #[test]
+    fn lines_and_values() {
+        // Echoes input until a '!', then prints "ok" and a value, then "end" without a newline.
+        let program = assemble(
+            "loop:   IN -> [c]
+                     EQ [c], #33 -> [done]
+                     JT [done], #finish
+                     OUT [c]
+                     JT #1, #loop
+             finish: OUT #111
+                     OUT #107
+                     OUT #10
+                     OUT #1234
+                     OUT #101
+                     OUT #110
+                     OUT #100
+                     HALT
+             c:      data 0
+             done:   data 0",
+        )
+        .unwrap();
+        let mut ascii = AsciiProgram::new(Program::new(program));
+
+        ascii.send_line("hi");
+        assert_eq!(
+            ascii.read_until_blocked(),
+            Ok(vec![
+                AsciiOutput::Line("hi".to_string()),
+                AsciiOutput::NeedsInput
+            ])
+        );
+
+        ascii.send("a!");
+        assert_eq!(
+            ascii.read_until_blocked(),
+            Ok(vec![
+                AsciiOutput::Line("aok".to_string()),
+                AsciiOutput::Value(1234),
+                AsciiOutput::Line("end".to_string()),
+                AsciiOutput::Halted
+            ])
+        );
+        assert_eq!(ascii.next_output(), Ok(AsciiOutput::Halted));
+    }
+}
@@ -0,0 +1,617 @@
+//! An assembler for a small Intcode assembly language.
+//!
+//! Each line holds any number of labels followed by an optional statement, and anything after a
+//! `;` is a comment:
+//!
+//! ```text
+//! loop:   IN -> [value]            ; position operand
+//!         ADD [value], #-1 -> [rb+2]
+//!         JT [value], #loop        ; immediate operand holding a label's address
+//!         HALT
+//! value:  data 0
+//! table:  data 1, 2, table+1
+//! ```
+//!
+//! Mnemonics are the ones produced by the disassembler and are case-insensitive. Operands are
+//! separated by `,`, except that the operand an instruction writes to may be marked with `->`
+//! instead. They are written `#x` (immediate), `[x]` (position) or `[rb+n]` (relative), where
+//! `x` is an integer, a label, or a label plus or minus an integer.
+
+use crate::{Instruction, OpCode, Parameter, ParameterMode};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+/// An assembly error, located by 1-based line and column.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AsmError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl AsmError {
+    fn new(line: usize, column: usize, message: impl Into<String>) -> Self {
+        AsmError {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+/// Assembles `source` into a program image.
+pub fn assemble(source: &str) -> Result<Vec<i64>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+    let mut address = 0;
+
+    for (i, text) in source.lines().enumerate() {
+        let line = parse_line(i + 1, text)?;
+
+        for (name, column) in line.labels {
+            if labels.insert(name.clone(), address).is_some() {
+                return Err(AsmError::new(
+                    i + 1,
+                    column,
+                    format!("label `{}` is already defined", name),
+                ));
+            }
+        }
+
+        if let Some(statement) = line.statement {
+            address += statement.len();
+            statements.push((i + 1, statement));
+        }
+    }
+
+    let mut memory = Vec::with_capacity(address);
+    for (line, statement) in statements {
+        match statement {
+            Statement::Instruction { opcode, operands } => {
+                let mut parameters = Vec::new();
+                for operand in operands {
+                    let value = operand.value.resolve(line, &labels)?;
+                    parameters.push(Parameter::new(value, operand.mode));
+                }
+                memory.extend(Instruction::new(opcode, &parameters).encode());
+            }
+            Statement::Data(values) => {
+                for value in values {
+                    memory.push(value.resolve(line, &labels)?);
+                }
+            }
+        }
+    }
+
+    Ok(memory)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    Ident(String),
+    Int(i64),
+    Arrow,
+    Colon,
+    Comma,
+    Hash,
+    LBracket,
+    RBracket,
+    Plus,
+    Minus,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenKind::Ident(name) => write!(f, "`{}`", name),
+            TokenKind::Int(n) => write!(f, "`{}`", n),
+            TokenKind::Arrow => write!(f, "`->`"),
+            TokenKind::Colon => write!(f, "`:`"),
+            TokenKind::Comma => write!(f, "`,`"),
+            TokenKind::Hash => write!(f, "`#`"),
+            TokenKind::LBracket => write!(f, "`[`"),
+            TokenKind::RBracket => write!(f, "`]`"),
+            TokenKind::Plus => write!(f, "`+`"),
+            TokenKind::Minus => write!(f, "`-`"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Token {
+    kind: TokenKind,
+    column: usize,
+}
+
+fn tokenize(line: usize, text: &str) -> Result<Vec<Token>, AsmError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+
+        if c == ';' {
+            break;
+        } else if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let kind = if c.is_ascii_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            TokenKind::Ident(chars[start..i].iter().collect())
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let digits: String = chars[start..i].iter().collect();
+            let n = digits
+                .parse()
+                .map_err(|_| AsmError::new(line, column, "integer is too large"))?;
+            TokenKind::Int(n)
+        } else {
+            i += 1;
+            match c {
+                '-' if chars.get(i) == Some(&'>') => {
+                    i += 1;
+                    TokenKind::Arrow
+                }
+                '-' => TokenKind::Minus,
+                '+' => TokenKind::Plus,
+                ':' => TokenKind::Colon,
+                ',' => TokenKind::Comma,
+                '#' => TokenKind::Hash,
+                '[' => TokenKind::LBracket,
+                ']' => TokenKind::RBracket,
+                _ => {
+                    return Err(AsmError::new(
+                        line,
+                        column,
+                        format!("unexpected character `{}`", c),
+                    ))
+                }
+            }
+        };
+
+        tokens.push(Token { kind, column });
+    }
+
+    Ok(tokens)
+}
+
+/// An integer, or a label plus an integer offset, resolved once every label is known.
+#[derive(Clone, Debug, PartialEq)]
+struct Expr {
+    label: Option<(String, usize)>,
+    offset: i64,
+}
+
+impl Expr {
+    fn resolve(&self, line: usize, labels: &HashMap<String, usize>) -> Result<i64, AsmError> {
+        match &self.label {
+            None => Ok(self.offset),
+            Some((name, column)) => {
+                let address = labels.get(name).ok_or_else(|| {
+                    AsmError::new(line, *column, format!("undefined label `{}`", name))
+                })?;
+                i64::try_from(*address)
+                    .ok()
+                    .and_then(|address| address.checked_add(self.offset))
+                    .ok_or_else(|| {
+                        let message = format!("`{}{:+}` is too large", name, self.offset);
+                        AsmError::new(line, *column, message)
+                    })
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Operand {
+    mode: ParameterMode,
+    value: Expr,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Statement {
+    Instruction {
+        opcode: OpCode,
+        operands: Vec<Operand>,
+    },
+    Data(Vec<Expr>),
+}
+
+impl Statement {
+    /// The number of words the statement assembles to.
+    fn len(&self) -> usize {
+        match self {
+            Statement::Instruction { opcode, .. } => opcode.instr_len(),
+            Statement::Data(values) => values.len(),
+        }
+    }
+}
+
+struct ParsedLine {
+    labels: Vec<(String, usize)>,
+    statement: Option<Statement>,
+}
+
+struct LineParser {
+    tokens: Vec<Token>,
+    pos: usize,
+    line: usize,
+    end_column: usize,
+}
+
+fn parse_line(line: usize, text: &str) -> Result<ParsedLine, AsmError> {
+    let mut parser = LineParser {
+        tokens: tokenize(line, text)?,
+        pos: 0,
+        line,
+        end_column: text.chars().count() + 1,
+    };
+
+    let mut labels = Vec::new();
+    while let (Some(TokenKind::Ident(name)), Some(TokenKind::Colon)) =
+        (parser.peek_kind(0).cloned(), parser.peek_kind(1).cloned())
+    {
+        let column = parser.tokens[parser.pos].column;
+        if name.eq_ignore_ascii_case("rb") {
+            return Err(parser.error_at(column, "`rb` is reserved for relative operands"));
+        }
+        labels.push((name, column));
+        parser.pos += 2;
+    }
+
+    let statement = if parser.pos < parser.tokens.len() {
+        Some(parser.statement()?)
+    } else {
+        None
+    };
+
+    Ok(ParsedLine { labels, statement })
+}
+
+impl LineParser {
+    fn peek_kind(&self, ahead: usize) -> Option<&TokenKind> {
+        self.tokens.get(self.pos + ahead).map(|t| &t.kind)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map_or(self.end_column, |t| t.column)
+    }
+
+    fn error_at(&self, column: usize, message: impl Into<String>) -> AsmError {
+        AsmError::new(self.line, column, message)
+    }
+
+    fn unexpected(&self, expected: &str) -> AsmError {
+        let found = match self.tokens.get(self.pos) {
+            Some(token) => token.kind.to_string(),
+            None => "end of line".to_string(),
+        };
+        self.error_at(
+            self.column(),
+            format!("expected {}, found {}", expected, found),
+        )
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        if self.peek_kind(0) == Some(kind) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn statement(&mut self) -> Result<Statement, AsmError> {
+        let column = self.column();
+        let name = match self.next() {
+            Some(Token {
+                kind: TokenKind::Ident(name),
+                ..
+            }) => name,
+            _ => {
+                self.pos -= 1;
+                return Err(self.unexpected("a mnemonic or `data`"));
+            }
+        };
+
+        if name.eq_ignore_ascii_case("data") {
+            let mut values = vec![self.expr()?];
+            while self.eat(&TokenKind::Comma) {
+                values.push(self.expr()?);
+            }
+            self.end()?;
+            return Ok(Statement::Data(values));
+        }
+
+        let opcode = OpCode::ALL
+            .iter()
+            .copied()
+            .find(|opcode| opcode.mnemonic().eq_ignore_ascii_case(&name))
+            .ok_or_else(|| self.error_at(column, format!("unknown mnemonic `{}`", name)))?;
+
+        let mut operands = Vec::new();
+        for i in 0..opcode.num_params() {
+            // `->` may only mark the operand the instruction writes to.
+            let writes = opcode.output_param() == Some(i);
+            let arrow_column = self.column();
+            if self.eat(&TokenKind::Arrow) {
+                if !writes {
+                    return Err(self.error_at(
+                        arrow_column,
+                        format!(
+                            "`->` must come before the operand {} writes to",
+                            opcode.mnemonic()
+                        ),
+                    ));
+                }
+            } else if i > 0 && !self.eat(&TokenKind::Comma) {
+                return Err(self.unexpected(if writes { "`,` or `->`" } else { "`,`" }));
+            }
+
+            let operand_column = self.column();
+            let operand = self.operand()?;
+            if operand.mode == ParameterMode::Immediate && opcode.output_param() == Some(i) {
+                return Err(self.error_at(
+                    operand_column,
+                    format!("{} cannot write to an immediate operand", opcode.mnemonic()),
+                ));
+            }
+            operands.push(operand);
+        }
+        self.end()?;
+
+        Ok(Statement::Instruction { opcode, operands })
+    }
+
+    fn end(&self) -> Result<(), AsmError> {
+        if self.pos < self.tokens.len() {
+            return Err(self.unexpected("end of line"));
+        }
+        Ok(())
+    }
+
+    fn operand(&mut self) -> Result<Operand, AsmError> {
+        if self.eat(&TokenKind::Hash) {
+            return Ok(Operand {
+                mode: ParameterMode::Immediate,
+                value: self.expr()?,
+            });
+        }
+
+        if !self.eat(&TokenKind::LBracket) {
+            return Err(self.unexpected("an operand"));
+        }
+
+        let operand = match self.peek_kind(0) {
+            Some(TokenKind::Ident(name)) if name.eq_ignore_ascii_case("rb") => {
+                self.pos += 1;
+                let offset = match self.peek_kind(0) {
+                    Some(TokenKind::RBracket) => 0,
+                    Some(TokenKind::Plus) | Some(TokenKind::Minus) => self.signed_int()?,
+                    _ => return Err(self.unexpected("`+`, `-` or `]`")),
+                };
+                Operand {
+                    mode: ParameterMode::Relative,
+                    value: Expr {
+                        label: None,
+                        offset,
+                    },
+                }
+            }
+            _ => Operand {
+                mode: ParameterMode::Position,
+                value: self.expr()?,
+            },
+        };
+
+        if !self.eat(&TokenKind::RBracket) {
+            return Err(self.unexpected("`]`"));
+        }
+
+        Ok(operand)
+    }
+
+    /// `[+|-] (integer | label) [(+|-) integer]`
+    fn expr(&mut self) -> Result<Expr, AsmError> {
+        let column = self.column();
+        match self.peek_kind(0).cloned() {
+            Some(TokenKind::Ident(name)) if !name.eq_ignore_ascii_case("rb") => {
+                self.pos += 1;
+                let offset = match self.peek_kind(0) {
+                    Some(TokenKind::Plus) | Some(TokenKind::Minus) => self.signed_int()?,
+                    _ => 0,
+                };
+                Ok(Expr {
+                    label: Some((name, column)),
+                    offset,
+                })
+            }
+            Some(TokenKind::Int(_)) | Some(TokenKind::Plus) | Some(TokenKind::Minus) => Ok(Expr {
+                label: None,
+                offset: self.signed_int()?,
+            }),
+            _ => Err(self.unexpected("an integer or label")),
+        }
+    }
+
+    fn signed_int(&mut self) -> Result<i64, AsmError> {
+        let negative = if self.eat(&TokenKind::Minus) {
+            true
+        } else {
+            self.eat(&TokenKind::Plus);
+            false
+        };
+
+        match self.peek_kind(0) {
+            Some(&TokenKind::Int(n)) => {
+                self.pos += 1;
+                Ok(if negative { -n } else { n })
+            }
+            _ => Err(self.unexpected("an integer")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{disassemble, LineKind, Program, Queue};
+
+    /// Turns a program image back into assembly source via the disassembler.
+    fn to_source(memory: &[i64]) -> String {
+        disassemble(memory)
+            .iter()
+            .map(|line| match &line.kind {
+                LineKind::Instruction(instr) => instr.to_string(),
+                LineKind::Data => {
+                    let words: Vec<String> = line.words.iter().map(|w| w.to_string()).collect();
+                    format!("data {}", words.join(", "))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn assembles_instructions() {
+        assert_eq!(
+            assemble("MUL [4], #3 -> [4]\ndata 33"),
+            Ok(vec![1002, 4, 3, 4, 33])
+        );
+        assert_eq!(
+            assemble("add [rb-1], [rb] -> [rb+3] ; comment\nhalt"),
+            Ok(vec![22201, -1, 0, 3, 99])
+        );
+    }
+
+    #[test]
+    fn labels() {
+        let source = "
+            ; Counts down from the input, printing each value.
+            start:  IN -> [counter]
+            loop:   OUT [counter]
+                    ADD [counter], #-1 -> [counter]
+                    JT [counter], #loop
+                    HALT
+            counter: data 0
+            table:  data start, loop+1, counter-1
+        ";
+        let memory = assemble(source).unwrap();
+
+        assert_eq!(
+            memory,
+            vec![3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0, 0, 3, 11]
+        );
+
+        let output = Queue::new();
+        let mut program = Program::new(memory)
+            .with_input(Queue::from(vec![3]))
+            .with_output(output.clone());
+        program.run().unwrap();
+        assert_eq!(output.drain(), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            assemble("ADD [1], #2\n  FOO [1]"),
+            Err(AsmError::new(
+                1,
+                12,
+                "expected `,` or `->`, found end of line"
+            ))
+        );
+        assert_eq!(
+            assemble("HALT\n  FOO [1]"),
+            Err(AsmError::new(2, 3, "unknown mnemonic `FOO`"))
+        );
+        assert_eq!(
+            assemble("ADD #1, #2 -> #3"),
+            Err(AsmError::new(
+                1,
+                15,
+                "ADD cannot write to an immediate operand"
+            ))
+        );
+        assert_eq!(
+            assemble("OUT -> [1]"),
+            Err(AsmError::new(
+                1,
+                5,
+                "`->` must come before the operand OUT writes to"
+            ))
+        );
+        assert_eq!(
+            assemble("ADD [1] -> [2], [3]"),
+            Err(AsmError::new(
+                1,
+                9,
+                "`->` must come before the operand ADD writes to"
+            ))
+        );
+        assert_eq!(
+            assemble("JT #1, #nowhere"),
+            Err(AsmError::new(1, 9, "undefined label `nowhere`"))
+        );
+        assert_eq!(
+            assemble("HALT\nx: data x+9223372036854775807"),
+            Err(AsmError::new(2, 9, "`x+9223372036854775807` is too large"))
+        );
+        assert_eq!(
+            assemble("a: HALT\na: HALT"),
+            Err(AsmError::new(2, 1, "label `a` is already defined"))
+        );
+        assert_eq!(
+            assemble("OUT [1] $"),
+            Err(AsmError::new(1, 9, "unexpected character `$`"))
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let sources = [
+            "IN -> [9]\nMUL [9], #3 -> [9]\nOUT [9]\nHALT",
+            "ARB #10\nADD [rb-3], #-7 -> [rb+2]\nJF [rb+0], #0\nHALT\ndata 0, -1, 0",
+        ];
+
+        for source in sources.iter() {
+            let memory = assemble(source).unwrap();
+            assert_eq!(to_source(&memory), *source);
+            assert_eq!(assemble(&to_source(&memory)), Ok(memory));
+        }
+
+        let day_05: Vec<i64> = include_str!("../../day_05/input.txt")
+            .trim()
+            .split(',')
+            .map(|s| s.parse().unwrap())
+            .collect();
+        assert_eq!(assemble(&to_source(&day_05)), Ok(day_05));
+    }
+}
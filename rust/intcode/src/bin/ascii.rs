@@ -0,0 +1,38 @@
+//! Runs an ASCII Intcode program, such as the day 25 text adventure, in the terminal.
+//!
+//! Usage: `ascii <program file>`. Output is printed as text, except for values outside the
+//! ASCII range, which are printed as numbers on their own line. Whenever the program wants
+//! input, a line is read from stdin and sent to it. The session ends when the program halts or
+//! stdin is closed.
+
+use intcode::{AsciiOutput, AsciiProgram, Program};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::io::{self, BufRead, Write};
+use std::{env, fs};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = env::args().nth(1).ok_or("Usage: ascii <program file>")?;
+    let input = fs::read_to_string(path)?;
+    let mut program = AsciiProgram::new(Program::try_from(input.as_str())?);
+
+    let stdin = io::stdin();
+    loop {
+        match program.next_output()? {
+            AsciiOutput::Line(line) => println!("{}", line),
+            AsciiOutput::Value(value) => println!("{}", value),
+            AsciiOutput::NeedsInput => {
+                io::stdout().flush()?;
+
+                let mut line = String::new();
+                if stdin.lock().read_line(&mut line)? == 0 {
+                    break;
+                }
+                program.send_line(line.trim_end_matches(&['\r', '\n'][..]));
+            }
+            AsciiOutput::Halted => break,
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,18 @@
+//! Assembles an Intcode assembly file and prints the program as comma-separated integers.
+//!
+//! Usage: `asm <source file>`
+
+use intcode::assemble;
+use std::error::Error;
+use std::{env, fs};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = env::args().nth(1).ok_or("Usage: asm <source file>")?;
+    let source = fs::read_to_string(&path)?;
+    let memory = assemble(&source).map_err(|e| format!("{}:{}", path, e))?;
+
+    let words: Vec<String> = memory.iter().map(|w| w.to_string()).collect();
+    println!("{}", words.join(","));
+
+    Ok(())
+}
@@ -0,0 +1,25 @@
+//! Prints the control-flow graph of an Intcode program in Graphviz DOT format.
+//!
+//! Usage: `cfg <program file>`, e.g. `cfg input.txt | dot -Tsvg > cfg.svg`. The addresses of
+//! jumps whose targets couldn't be resolved are listed on stderr.
+
+use intcode::{control_flow_graph, Program};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::{env, fs};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = env::args().nth(1).ok_or("Usage: cfg <program file>")?;
+    let input = fs::read_to_string(path)?;
+    let program = Program::try_from(input.as_str())?;
+
+    let cfg = control_flow_graph(&program.memory().to_vec());
+    print!("{}", cfg.to_dot());
+
+    let unresolved = cfg.unresolved_jumps();
+    if !unresolved.is_empty() {
+        eprintln!("Unresolved jumps at: {:?}", unresolved);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,48 @@
+//! An interactive debugger for Intcode programs.
+//!
+//! Usage: `debugger <program file>`, then type `help` at the prompt. An empty line repeats the
+//! previous command.
+
+use intcode::{Debugger, Program};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::io::{self, BufRead, Write};
+use std::{env, fs};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = env::args().nth(1).ok_or("Usage: debugger <program file>")?;
+    let input = fs::read_to_string(path)?;
+    let program = Program::try_from(input.as_str())?;
+
+    let mut debugger = Debugger::new(program.memory().to_vec());
+    println!("{}", debugger.execute("where")?);
+
+    let stdin = io::stdin();
+    let mut last_command = String::new();
+    loop {
+        print!("(icdb) ");
+        io::stdout().flush()?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            break;
+        }
+
+        let command = match line.trim() {
+            "" => last_command.clone(),
+            command => command.to_string(),
+        };
+        if command == "q" || command == "quit" {
+            break;
+        }
+
+        match debugger.execute(&command) {
+            Ok(out) if out.is_empty() => {}
+            Ok(out) => println!("{}", out),
+            Err(e) => println!("error: {}", e),
+        }
+        last_command = command;
+    }
+
+    Ok(())
+}
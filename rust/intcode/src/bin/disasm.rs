@@ -0,0 +1,20 @@
+//! Prints an annotated listing of an Intcode program.
+//!
+//! Usage: `disasm <program file>`
+
+use intcode::{disassemble, Program};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::{env, fs};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = env::args().nth(1).ok_or("Usage: disasm <program file>")?;
+    let input = fs::read_to_string(path)?;
+    let program = Program::try_from(input.as_str())?;
+
+    for line in disassemble(&program.memory().to_vec()) {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,39 @@
+//! Runs an Intcode program and prints where it spent its time: executions per opcode, per
+//! parameter-mode combination and per address, and how often each conditional jump was taken.
+//! The program's own output goes to stderr.
+//!
+//! Usage: `profile [--csv] <program file> [input]...`
+
+use intcode::{Profiler, Program, Queue};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::{env, fs};
+
+/// How many of the busiest addresses the report lists.
+const REPORT_ADDRESSES: usize = 20;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1).peekable();
+    let csv = args.next_if(|a| a == "--csv").is_some();
+    let path = args
+        .next()
+        .ok_or("Usage: profile [--csv] <program file> [input]...")?;
+    let inputs = args.map(|a| a.parse()).collect::<Result<Vec<i64>, _>>()?;
+
+    let profiler = Profiler::new();
+    let source = fs::read_to_string(path)?;
+    let mut program = Program::try_from(source.as_str())?
+        .with_input(Queue::from(inputs))
+        .with_output(|value| eprintln!("{}", value))
+        .with_tracer(profiler.clone());
+    program.run()?;
+
+    let profile = profiler.profile();
+    if csv {
+        print!("{}", profile.to_csv());
+    } else {
+        print!("{}", profile.report(REPORT_ADDRESSES));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,27 @@
+//! Runs an Intcode program and writes a JSON Lines trace of every executed instruction to
+//! stdout. The program's own output goes to stderr.
+//!
+//! Usage: `trace <program file> [input]...`
+
+use intcode::{JsonLinesTracer, Program, Queue};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::io::{self, BufWriter};
+use std::{env, fs};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let path = args
+        .next()
+        .ok_or("Usage: trace <program file> [input]...")?;
+    let inputs = args.map(|a| a.parse()).collect::<Result<Vec<i64>, _>>()?;
+
+    let source = fs::read_to_string(path)?;
+    let mut program = Program::try_from(source.as_str())?
+        .with_input(Queue::from(inputs))
+        .with_output(|value| eprintln!("{}", value))
+        .with_tracer(JsonLinesTracer::new(BufWriter::new(io::stdout())));
+    program.run()?;
+
+    Ok(())
+}
@@ -0,0 +1,21 @@
+//! Translates an Intcode program into a standalone Rust program and prints its source.
+//!
+//! Usage: `transpile <program file> > program.rs`, then `rustc -O program.rs` and run
+//! `./program [input]...`. Each output is printed on its own line.
+
+use intcode::{transpile, Program};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::{env, fs};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = env::args()
+        .nth(1)
+        .ok_or("Usage: transpile <program file>")?;
+    let input = fs::read_to_string(path)?;
+    let program = Program::try_from(input.as_str())?;
+
+    print!("{}", transpile(&program.memory().to_vec()));
+
+    Ok(())
+}
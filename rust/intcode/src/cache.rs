@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+/// The number of entries in each page. Smaller than a page of memory, since entries are
+/// bigger and forks copy a whole page to change one entry in it.
+const PAGE_SIZE: usize = 64;
+
+type Page<T> = [Option<T>; PAGE_SIZE];
+
+/// Values cached by address, such as decoded instructions, stored as shared fixed-size pages.
+///
+/// Unlike `Memory`, the page table is shared too, since most forks never change the cache.
+/// Cloning only copies a pointer. The first change a clone makes copies the page table, and
+/// the first change to each page copies that page.
+#[derive(Clone)]
+pub(crate) struct Cache<T> {
+    pages: Arc<Vec<Arc<Page<T>>>>,
+}
+
+impl<T: Copy> Cache<T> {
+    pub(crate) fn new() -> Self {
+        Cache {
+            pages: Arc::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn get(&self, address: usize) -> Option<&T> {
+        self.pages.get(address / PAGE_SIZE)?[address % PAGE_SIZE].as_ref()
+    }
+
+    pub(crate) fn insert(&mut self, address: usize, value: T) {
+        let (page, pages) = (address / PAGE_SIZE, Arc::make_mut(&mut self.pages));
+        if page >= pages.len() {
+            pages.resize_with(page + 1, || Arc::new([None; PAGE_SIZE]));
+        }
+
+        Arc::make_mut(&mut pages[page])[address % PAGE_SIZE] = Some(value);
+    }
+
+    /// Drops the entry at `address`. Nothing shared with a clone is copied unless the entry
+    /// was there.
+    pub(crate) fn remove(&mut self, address: usize) {
+        if self.get(address).is_some() {
+            let page = &mut Arc::make_mut(&mut self.pages)[address / PAGE_SIZE];
+            Arc::make_mut(page)[address % PAGE_SIZE] = None;
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.pages = Arc::new(Vec::new());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn clones_share_untouched_pages() {
+        let mut original = Cache::new();
+        for address in 0..3 * PAGE_SIZE - 1 {
+            original.insert(address, address);
+        }
+
+        // Removing entries that aren't there leaves everything shared.
+        let mut copy = original.clone();
+        copy.remove(3 * PAGE_SIZE - 1);
+        copy.remove(5 * PAGE_SIZE);
+        assert!(Arc::ptr_eq(&original.pages, &copy.pages));
+
+        copy.remove(PAGE_SIZE + 1);
+        assert_eq!(original.get(PAGE_SIZE + 1), Some(&(PAGE_SIZE + 1)));
+        assert_eq!(copy.get(PAGE_SIZE + 1), None);
+
+        let shared: Vec<bool> = (0..3)
+            .map(|i| Arc::ptr_eq(&original.pages[i], &copy.pages[i]))
+            .collect();
+        assert_eq!(shared, vec![true, false, true]);
+    }
+}
@@ -0,0 +1,314 @@
+use crate::{Instruction, OpCode, ParameterMode};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::Write;
+
+/// A run of instructions that is only ever entered at its first instruction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BasicBlock {
+    pub start: usize,
+    /// Each instruction along with its address.
+    pub instructions: Vec<(usize, Instruction)>,
+    pub exit: BlockExit,
+}
+
+/// How control leaves a basic block.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BlockExit {
+    Halt,
+    /// The last instruction runs into the start of another block.
+    FallThrough,
+    /// A jump to an immediate target. Conditional jumps also fall through to the next
+    /// instruction.
+    Jump,
+    /// A jump whose target is read from memory, so where it goes isn't known. Conditional jumps
+    /// still fall through to the next instruction.
+    UnresolvedJump,
+    /// Execution runs into a word that doesn't decode as an instruction.
+    Invalid,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum EdgeKind {
+    /// A jump that is taken.
+    Taken,
+    /// A conditional jump that isn't taken.
+    NotTaken,
+    /// A block running into the next one.
+    FallThrough,
+}
+
+/// An edge between the blocks starting at `from` and `to`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/// The control-flow graph of the code reachable from address 0.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ControlFlowGraph {
+    /// Blocks by their start address.
+    pub blocks: BTreeMap<usize, BasicBlock>,
+    pub edges: Vec<Edge>,
+}
+
+/// Where a jump instruction can go when it is taken.
+enum Taken {
+    Never,
+    To(usize),
+    Unresolved,
+}
+
+/// Where the jump at `address` goes when taken, and the next address if it can fall through.
+/// Conditions given as immediates are known ahead of time.
+fn jump_targets(address: usize, instr: &Instruction) -> (Taken, Option<usize>) {
+    let [condition, target] = [instr.parameters()[0], instr.parameters()[1]];
+    let jump_if = instr.opcode == OpCode::JumpIfTrue;
+
+    let (can_jump, can_fall_through) = match condition.mode {
+        ParameterMode::Immediate => {
+            let jumps = (condition.value != 0) == jump_if;
+            (jumps, !jumps)
+        }
+        _ => (true, true),
+    };
+
+    let taken = if !can_jump {
+        Taken::Never
+    } else if target.mode == ParameterMode::Immediate && target.value >= 0 {
+        Taken::To(target.value as usize)
+    } else {
+        Taken::Unresolved
+    };
+    let next = Some(address + instr.opcode.instr_len()).filter(|_| can_fall_through);
+
+    (taken, next)
+}
+
+/// Builds the control-flow graph of `memory` by following every path from address 0 without
+/// running anything.
+///
+/// Only jumps with immediate targets can be followed. Anything reached through a jump whose
+/// target is read from memory is missed, as is any code the program writes for itself.
+pub fn control_flow_graph(memory: &[i64]) -> ControlFlowGraph {
+    // Find every address a block can start at: the entry point, jump targets and the
+    // instructions after conditional jumps.
+    let mut leaders = BTreeSet::new();
+    leaders.insert(0);
+    let mut visited = HashSet::new();
+    let mut pending = vec![0];
+
+    while let Some(address) = pending.pop() {
+        if !visited.insert(address) {
+            continue;
+        }
+        let instr = match Instruction::decode(memory, address) {
+            Ok(instr) => instr,
+            Err(_) => continue,
+        };
+
+        match instr.opcode {
+            OpCode::Halt => {}
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+                let (taken, next) = jump_targets(address, &instr);
+                let targets = match taken {
+                    Taken::To(target) => vec![Some(target), next],
+                    _ => vec![next],
+                };
+                for target in targets.into_iter().flatten() {
+                    leaders.insert(target);
+                    pending.push(target);
+                }
+            }
+            opcode => pending.push(address + opcode.instr_len()),
+        }
+    }
+
+    let mut cfg = ControlFlowGraph::default();
+    for &start in &leaders {
+        let mut instructions = Vec::new();
+        let mut address = start;
+
+        let exit = loop {
+            if address != start && leaders.contains(&address) {
+                cfg.edges.push(Edge {
+                    from: start,
+                    to: address,
+                    kind: EdgeKind::FallThrough,
+                });
+                break BlockExit::FallThrough;
+            }
+            let instr = match Instruction::decode(memory, address) {
+                Ok(instr) => instr,
+                Err(_) => break BlockExit::Invalid,
+            };
+            instructions.push((address, instr));
+
+            match instr.opcode {
+                OpCode::Halt => break BlockExit::Halt,
+                OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+                    let (taken, next) = jump_targets(address, &instr);
+                    let mut edge = |to, kind| {
+                        cfg.edges.push(Edge {
+                            from: start,
+                            to,
+                            kind,
+                        })
+                    };
+                    if let Taken::To(target) = taken {
+                        edge(target, EdgeKind::Taken);
+                    }
+                    if let Some(next) = next {
+                        edge(next, EdgeKind::NotTaken);
+                    }
+
+                    break match taken {
+                        Taken::Unresolved => BlockExit::UnresolvedJump,
+                        _ => BlockExit::Jump,
+                    };
+                }
+                opcode => address += opcode.instr_len(),
+            }
+        };
+
+        cfg.blocks.insert(
+            start,
+            BasicBlock {
+                start,
+                instructions,
+                exit,
+            },
+        );
+    }
+
+    cfg
+}
+
+impl ControlFlowGraph {
+    /// The addresses of jumps whose targets couldn't be worked out.
+    pub fn unresolved_jumps(&self) -> Vec<usize> {
+        self.blocks
+            .values()
+            .filter(|block| block.exit == BlockExit::UnresolvedJump)
+            .filter_map(|block| block.instructions.last().map(|&(address, _)| address))
+            .collect()
+    }
+
+    /// The graph in Graphviz DOT format, e.g. for `dot -Tsvg`. Blocks ending in an unresolved
+    /// jump or an invalid instruction are drawn in red.
+    pub fn to_dot(&self) -> String {
+        let mut out =
+            String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n");
+
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            for (address, instr) in &block.instructions {
+                write!(label, "{}: {}\\l", address, instr).unwrap();
+            }
+            let attributes = match block.exit {
+                BlockExit::UnresolvedJump => {
+                    label.push_str("unresolved jump\\l");
+                    ", color=red"
+                }
+                BlockExit::Invalid => {
+                    label.push_str("invalid instruction\\l");
+                    ", color=red"
+                }
+                BlockExit::Halt => ", peripheries=2",
+                _ => "",
+            };
+            writeln!(
+                out,
+                "    b{} [label=\"{}\"{}];",
+                block.start, label, attributes
+            )
+            .unwrap();
+        }
+
+        for edge in &self.edges {
+            let attributes = match edge.kind {
+                EdgeKind::Taken => "label=\"taken\"",
+                EdgeKind::NotTaken => "label=\"not taken\", style=dashed",
+                EdgeKind::FallThrough => "style=dashed",
+            };
+            writeln!(out, "    b{} -> b{} [{}];", edge.from, edge.to, attributes).unwrap();
+        }
+
+        out.push_str("}\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::assemble;
+
+    fn program() -> Vec<i64> {
+        assemble(
+            "       IN -> [n]
+             loop:  JF [n], #done
+                    ADD [n], #-1 -> [n]
+                    JT #1, #loop
+             done:  JT [n], [n]
+                    HALT
+             n:     data 0",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn blocks_and_edges() {
+        let cfg = control_flow_graph(&program());
+
+        let exits: Vec<_> = cfg
+            .blocks
+            .values()
+            .map(|block| (block.start, block.instructions.len(), block.exit))
+            .collect();
+        assert_eq!(
+            exits,
+            vec![
+                (0, 1, BlockExit::FallThrough),
+                (2, 1, BlockExit::Jump),
+                (5, 2, BlockExit::Jump),
+                (12, 1, BlockExit::UnresolvedJump),
+                (15, 1, BlockExit::Halt),
+            ]
+        );
+
+        let edge = |from, to, kind| Edge { from, to, kind };
+        assert_eq!(
+            cfg.edges,
+            vec![
+                edge(0, 2, EdgeKind::FallThrough),
+                edge(2, 12, EdgeKind::Taken),
+                edge(2, 5, EdgeKind::NotTaken),
+                edge(5, 2, EdgeKind::Taken),
+                edge(12, 15, EdgeKind::NotTaken),
+            ]
+        );
+        assert_eq!(cfg.unresolved_jumps(), vec![12]);
+    }
+
+    #[test]
+    fn invalid_code_and_dot() {
+        // Jumps over a HALT into a word that doesn't decode.
+        let cfg = control_flow_graph(&[1105, 1, 4, 99, 0]);
+
+        assert_eq!(cfg.blocks.len(), 2);
+        assert_eq!(cfg.blocks[&4].exit, BlockExit::Invalid);
+        assert!(cfg.blocks[&4].instructions.is_empty());
+
+        assert_eq!(
+            cfg.to_dot(),
+            "digraph cfg {\n    \
+                 node [shape=box, fontname=\"monospace\"];\n    \
+                 b0 [label=\"0: JT #1, #4\\l\"];\n    \
+                 b4 [label=\"invalid instruction\\l\", color=red];\n    \
+                 b0 -> b4 [label=\"taken\"];\n\
+             }\n"
+        );
+    }
+}
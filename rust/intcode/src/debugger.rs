@@ -0,0 +1,508 @@
+use crate::program::MEMORY_LIMIT;
+use crate::{Event, Instruction, MemoryWrite, OpCode, Program, Queue, Status};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+
+const HELP: &str = "\
+commands:
+  step [n]              execute n instructions (default 1)
+  continue              run until a breakpoint, input is needed, or the program halts
+  back [n]              undo the last n instructions (default 1)
+  lastwrite <addr>      run backwards to the instruction that last wrote to addr
+  break <addr|op>       break at an address, or on every instruction with a mnemonic
+  delete <addr|op>      remove a breakpoint
+  watch <addr>          break when an instruction changes the value at an address
+  unwatch <addr>        remove a watchpoint
+  breakpoints           list breakpoints and watchpoints
+  where                 show the instruction pointer, relative base and current instruction
+  list [addr] [n]       disassemble n instructions from addr (default: 10 from the pointer)
+  dump <addr> [n]       print n memory words from addr (default 8)
+  patch <addr> <v>...   write values to memory starting at addr (not undone by back)
+  input <v>...          queue values for the program's input instructions
+  quit                  exit the debugger";
+
+/// An interactive debugger around a `Program`.
+///
+/// Commands are given as text to `execute`, which returns what should be shown to the user.
+/// The program's input comes from values queued with the `input` command, and its output is
+/// reported as it is produced.
+///
+/// Patches aren't instructions, so they aren't recorded in the program's history and `back`
+/// doesn't undo them.
+pub struct Debugger {
+    program: Program,
+    input: Queue,
+    output: Queue,
+    address_breakpoints: BTreeSet<usize>,
+    opcode_breakpoints: Vec<OpCode>,
+    watchpoints: Arc<Mutex<BTreeSet<usize>>>,
+    /// Changes to watched addresses made since the last command.
+    watch_hits: Arc<Mutex<Vec<MemoryWrite>>>,
+}
+
+impl Debugger {
+    pub fn new(memory: Vec<i64>) -> Self {
+        let input = Queue::new();
+        let output = Queue::new();
+        let watchpoints = Arc::new(Mutex::new(BTreeSet::new()));
+        let watch_hits = Arc::new(Mutex::new(Vec::new()));
+
+        let (watched, hits) = (watchpoints.clone(), watch_hits.clone());
+        let program = Program::new(memory)
+            .with_history()
+            .with_input(input.clone())
+            .with_output(output.clone())
+            .with_observer(.., move |event: &Event| {
+                if let Event::Write { write, .. } = *event {
+                    if write.old != write.new && watched.lock().unwrap().contains(&write.address) {
+                        hits.lock().unwrap().push(write);
+                    }
+                }
+            });
+
+        Debugger {
+            program,
+            input,
+            output,
+            address_breakpoints: BTreeSet::new(),
+            opcode_breakpoints: Vec::new(),
+            watchpoints,
+            watch_hits,
+        }
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// Runs one debugger command, returning its output or an error message.
+    pub fn execute(&mut self, command: &str) -> Result<String, String> {
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let args: Vec<&str> = words.collect();
+
+        match name {
+            "s" | "step" => {
+                let count = match args.first() {
+                    Some(n) => parse_number(n)?,
+                    None => 1,
+                };
+                Ok(self.run(Some(count)))
+            }
+            "c" | "continue" => Ok(self.run(None)),
+            "back" => {
+                let count = match args.first() {
+                    Some(n) => parse_number(n)?,
+                    None => 1,
+                };
+                let undone = (0..count).take_while(|_| self.program.step_back()).count();
+                if undone < count {
+                    Ok(format!(
+                        "reached the start of history\n{}",
+                        self.current_line()
+                    ))
+                } else {
+                    Ok(self.current_line())
+                }
+            }
+            "lastwrite" => {
+                let address = parse_address(args.first().ok_or("lastwrite needs an address")?)?;
+                match self.program.run_back_to_write(address) {
+                    Some(write) => Ok(format!(
+                        "[{}] was changed from {} to {} by\n{}",
+                        address,
+                        write.old,
+                        write.new,
+                        self.current_line()
+                    )),
+                    None => Err(format!(
+                        "nothing wrote to {} (now at the start of history)",
+                        address
+                    )),
+                }
+            }
+            "b" | "break" => {
+                let arg = args.first().ok_or("break needs an address or mnemonic")?;
+                match parse_opcode(arg) {
+                    Some(opcode) => {
+                        if !self.opcode_breakpoints.contains(&opcode) {
+                            self.opcode_breakpoints.push(opcode);
+                        }
+                    }
+                    None => {
+                        self.address_breakpoints.insert(parse_address(arg)?);
+                    }
+                }
+                Ok(format!("breakpoint set on {}", arg))
+            }
+            "d" | "delete" => {
+                let arg = args.first().ok_or("delete needs an address or mnemonic")?;
+                let removed = match parse_opcode(arg) {
+                    Some(opcode) => {
+                        let before = self.opcode_breakpoints.len();
+                        self.opcode_breakpoints.retain(|&op| op != opcode);
+                        self.opcode_breakpoints.len() != before
+                    }
+                    None => self.address_breakpoints.remove(&parse_address(arg)?),
+                };
+                if removed {
+                    Ok(format!("breakpoint on {} deleted", arg))
+                } else {
+                    Err(format!("no breakpoint on {}", arg))
+                }
+            }
+            "watch" => {
+                let address = parse_address(args.first().ok_or("watch needs an address")?)?;
+                self.watchpoints.lock().unwrap().insert(address);
+                Ok(format!("watchpoint set on {}", address))
+            }
+            "unwatch" => {
+                let address = parse_address(args.first().ok_or("unwatch needs an address")?)?;
+                if self.watchpoints.lock().unwrap().remove(&address) {
+                    Ok(format!("watchpoint on {} deleted", address))
+                } else {
+                    Err(format!("no watchpoint on {}", address))
+                }
+            }
+            "breakpoints" => {
+                let mut out = String::new();
+                for address in &self.address_breakpoints {
+                    writeln!(out, "address {}", address).unwrap();
+                }
+                for opcode in &self.opcode_breakpoints {
+                    writeln!(out, "opcode {}", opcode.mnemonic()).unwrap();
+                }
+                for address in self.watchpoints.lock().unwrap().iter() {
+                    writeln!(out, "watch {}", address).unwrap();
+                }
+                if out.is_empty() {
+                    out.push_str("no breakpoints");
+                }
+                Ok(out.trim_end().to_string())
+            }
+            "w" | "where" => Ok(format!(
+                "ip = {}, rb = {}\n{}",
+                self.program.instr_ptr(),
+                self.program.relative_base(),
+                self.current_line()
+            )),
+            "l" | "list" => {
+                let start = match args.first() {
+                    Some(a) => parse_address(a)?,
+                    None => self.program.instr_ptr(),
+                };
+                let count = match args.get(1) {
+                    Some(n) => parse_number(n)?,
+                    None => 10,
+                };
+                Ok(self.list(start, count))
+            }
+            "x" | "dump" => {
+                let start = parse_address(args.first().ok_or("dump needs an address")?)?;
+                let count = match args.get(1) {
+                    Some(n) => parse_number(n)?,
+                    None => 8,
+                };
+                Ok(self.dump(start, count))
+            }
+            "p" | "patch" => {
+                let start = parse_address(args.first().ok_or("patch needs an address")?)?;
+                if args.len() < 2 {
+                    return Err("patch needs at least one value".to_string());
+                }
+                if start + args.len() - 1 > MEMORY_LIMIT {
+                    return Err(format!(
+                        "patch would write past address {}",
+                        MEMORY_LIMIT - 1
+                    ));
+                }
+                // Nothing is written unless every value parses.
+                let values = args[1..]
+                    .iter()
+                    .map(|value| parse_value(value))
+                    .collect::<Result<Vec<_>, _>>()?;
+                for (i, &value) in values.iter().enumerate() {
+                    self.program.write(start + i, value);
+                }
+                Ok(self.dump(start, args.len() - 1))
+            }
+            "i" | "input" => {
+                for value in &args {
+                    self.input.push(parse_value(value)?);
+                }
+                Ok(format!("{} value(s) queued", self.input.len()))
+            }
+            "h" | "help" => Ok(HELP.to_string()),
+            "" => Ok(String::new()),
+            _ => Err(format!("unknown command `{}` (try `help`)", name)),
+        }
+    }
+
+    /// Steps `limit` instructions, or until something stops execution if there is no limit.
+    fn run(&mut self, limit: Option<usize>) -> String {
+        let mut out = String::new();
+        let mut steps = 0;
+
+        while limit != Some(0) {
+            let status = self.program.step();
+            for value in self.output.drain() {
+                writeln!(out, "output: {}", value).unwrap();
+            }
+            let hits: Vec<_> = self.watch_hits.lock().unwrap().drain(..).collect();
+            for hit in &hits {
+                writeln!(
+                    out,
+                    "watchpoint: [{}] changed from {} to {}",
+                    hit.address, hit.old, hit.new
+                )
+                .unwrap();
+            }
+
+            match status {
+                Err(e) => {
+                    writeln!(out, "error: {}", e).unwrap();
+                    break;
+                }
+                Ok(Some(Status::Halted)) => {
+                    writeln!(out, "program halted").unwrap();
+                    break;
+                }
+                Ok(Some(Status::NeedsInput)) => {
+                    writeln!(out, "waiting for input (queue some with `input`)").unwrap();
+                    break;
+                }
+                Ok(_) => {}
+            }
+
+            steps += 1;
+            if limit == Some(steps) {
+                break;
+            }
+            if limit.is_none() && !hits.is_empty() {
+                break;
+            }
+            if limit.is_none() && self.at_breakpoint() {
+                writeln!(out, "breakpoint hit").unwrap();
+                break;
+            }
+        }
+
+        out.push_str(&self.current_line());
+        out
+    }
+
+    fn at_breakpoint(&self) -> bool {
+        if self.address_breakpoints.contains(&self.program.instr_ptr()) {
+            return true;
+        }
+
+        match self.program.current_instruction() {
+            Ok(instr) => self.opcode_breakpoints.contains(&instr.opcode),
+            Err(_) => false,
+        }
+    }
+
+    fn current_line(&self) -> String {
+        self.list(self.program.instr_ptr(), 1)
+    }
+
+    fn list(&self, start: usize, count: usize) -> String {
+        let mut lines = Vec::new();
+        let mut address = start;
+
+        for _ in 0..count {
+            if address >= MEMORY_LIMIT {
+                break;
+            }
+            let marker = if address == self.program.instr_ptr() {
+                "=>"
+            } else {
+                "  "
+            };
+
+            match Instruction::decode_with(|a| self.program.read(a), address) {
+                Ok(instr) => {
+                    lines.push(format!("{} {:>5}: {}", marker, address, instr));
+                    address += instr.opcode.instr_len();
+                }
+                Err(_) => {
+                    lines.push(format!(
+                        "{} {:>5}: DATA {}",
+                        marker,
+                        address,
+                        self.program.read(address)
+                    ));
+                    address += 1;
+                }
+            }
+        }
+
+        lines.join("\n")
+    }
+
+    fn dump(&self, start: usize, count: usize) -> String {
+        let mut lines = Vec::new();
+        let end = start.saturating_add(count).min(MEMORY_LIMIT);
+
+        for row_start in (start..end).step_by(8) {
+            let row_end = (row_start + 8).min(end);
+            let words: Vec<String> = (row_start..row_end)
+                .map(|a| self.program.read(a).to_string())
+                .collect();
+            lines.push(format!("{:>5}: {}", row_start, words.join(" ")));
+        }
+
+        lines.join("\n")
+    }
+}
+
+fn parse_opcode(s: &str) -> Option<OpCode> {
+    OpCode::ALL
+        .iter()
+        .copied()
+        .find(|opcode| opcode.mnemonic().eq_ignore_ascii_case(s))
+}
+
+fn parse_number(s: &str) -> Result<usize, String> {
+    s.parse()
+        .map_err(|_| format!("`{}` is not an address or count", s))
+}
+
+/// Rejects addresses that no program can use.
+fn parse_address(s: &str) -> Result<usize, String> {
+    match parse_number(s)? {
+        address if address < MEMORY_LIMIT => Ok(address),
+        _ => Err(format!(
+            "`{}` is past the last address, {}",
+            s,
+            MEMORY_LIMIT - 1
+        )),
+    }
+}
+
+fn parse_value(s: &str) -> Result<i64, String> {
+    s.parse().map_err(|_| format!("`{}` is not an integer", s))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn debugger(source: &str) -> Debugger {
+        Debugger::new(source.split(',').map(|s| s.parse().unwrap()).collect())
+    }
+
+    #[test]
+    fn step_and_where() {
+        let mut dbg = debugger("1101,2,3,9,4,9,99,0,0,0");
+
+        assert_eq!(dbg.execute("step"), Ok("=>     4: OUT [9]".to_string()));
+        assert_eq!(
+            dbg.execute("step"),
+            Ok("output: 5\n=>     6: HALT".to_string())
+        );
+        assert_eq!(
+            dbg.execute("where"),
+            Ok("ip = 6, rb = 0\n=>     6: HALT".to_string())
+        );
+        assert_eq!(
+            dbg.execute("step"),
+            Ok("program halted\n=>     6: HALT".to_string())
+        );
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut dbg = debugger("1101,2,3,11,4,11,1101,0,0,11,99,0");
+
+        dbg.execute("break 6").unwrap();
+        assert_eq!(
+            dbg.execute("continue"),
+            Ok("output: 5\nbreakpoint hit\n=>     6: ADD #0, #0 -> [11]".to_string())
+        );
+        assert!(dbg.execute("delete 6").is_ok());
+        assert!(dbg.execute("delete 6").is_err());
+
+        let mut dbg = debugger("1101,2,3,11,4,11,1101,0,0,11,99,0");
+        dbg.execute("break out").unwrap();
+        assert_eq!(
+            dbg.execute("c"),
+            Ok("breakpoint hit\n=>     4: OUT [11]".to_string())
+        );
+        assert_eq!(dbg.execute("breakpoints"), Ok("opcode OUT".to_string()));
+    }
+
+    #[test]
+    fn watchpoints() {
+        // Counts address 11 down from 2, then outputs it.
+        let mut dbg = debugger("1001,11,-1,11,1005,11,0,4,11,99,0,2");
+
+        dbg.execute("watch 11").unwrap();
+        assert_eq!(
+            dbg.execute("continue"),
+            Ok("watchpoint: [11] changed from 2 to 1\n=>     4: JT [11], #0".to_string())
+        );
+        assert_eq!(
+            dbg.execute("step 3"),
+            Ok("watchpoint: [11] changed from 1 to 0\n=>     7: OUT [11]".to_string())
+        );
+        assert_eq!(dbg.execute("breakpoints"), Ok("watch 11".to_string()));
+
+        // Patching memory from the debugger doesn't trigger watchpoints.
+        dbg.execute("patch 11 5").unwrap();
+        assert_eq!(
+            dbg.execute("step"),
+            Ok("output: 5\n=>     9: HALT".to_string())
+        );
+        assert!(dbg.execute("unwatch 11").is_ok());
+        assert!(dbg.execute("unwatch 11").is_err());
+    }
+
+    #[test]
+    fn reverse_execution() {
+        let mut dbg = debugger("1101,2,3,11,4,11,1101,0,0,11,99,0");
+
+        dbg.execute("continue").unwrap();
+        assert_eq!(
+            dbg.execute("lastwrite 11"),
+            Ok("[11] was changed from 5 to 0 by\n=>     6: ADD #0, #0 -> [11]".to_string())
+        );
+        assert_eq!(dbg.execute("back"), Ok("=>     4: OUT [11]".to_string()));
+        assert_eq!(
+            dbg.execute("back 5"),
+            Ok("reached the start of history\n=>     0: ADD #2, #3 -> [11]".to_string())
+        );
+        assert!(dbg.execute("lastwrite 11").is_err());
+    }
+
+    #[test]
+    fn input_dump_and_patch() {
+        let mut dbg = debugger("3,7,4,7,99,0,0,0");
+
+        assert_eq!(
+            dbg.execute("continue"),
+            Ok("waiting for input (queue some with `input`)\n=>     0: IN -> [7]".to_string())
+        );
+        dbg.execute("input 42").unwrap();
+        dbg.execute("step").unwrap();
+        assert_eq!(dbg.execute("dump 4 4"), Ok("    4: 99 0 0 42".to_string()));
+        assert_eq!(dbg.execute("patch 7 -1"), Ok("    7: -1".to_string()));
+        assert_eq!(
+            dbg.execute("c"),
+            Ok("output: -1\nprogram halted\n=>     4: HALT".to_string())
+        );
+        assert!(dbg.execute("frobnicate").is_err());
+
+        // A bad value leaves memory untouched.
+        assert!(dbg.execute("patch 5 1 x").is_err());
+        assert_eq!(dbg.execute("dump 5 2"), Ok("    5: 0 0".to_string()));
+        assert!(dbg.execute("patch 99999999999 1").is_err());
+        assert!(dbg.execute("patch 1048575 1 2").is_err());
+        assert_eq!(dbg.program().memory().len(), 8);
+        assert_eq!(
+            dbg.execute("dump 1048574 18446744073709551615"),
+            Ok("1048574: 0 0".to_string())
+        );
+    }
+}
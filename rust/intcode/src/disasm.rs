@@ -0,0 +1,127 @@
+use crate::Instruction;
+use std::fmt;
+
+/// The most data words shown on a single listing line.
+const MAX_DATA_WORDS: usize = 8;
+
+/// One line of a disassembly listing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    pub address: usize,
+    /// The raw words this line covers.
+    pub words: Vec<i64>,
+    pub kind: LineKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LineKind {
+    Instruction(Instruction),
+    /// Words that do not decode as an instruction.
+    Data,
+}
+
+/// Disassembles a program image with a linear sweep from address 0.
+///
+/// Words that fail to decode, carry mode digits for parameters the opcode doesn't have, or whose
+/// parameters would run past the end of the image are grouped into `Data` lines. Data that
+/// happens to decode (e.g. a stray `1` after a `HALT`) is still shown as an instruction, since
+/// there is no way to tell the two apart without running the program.
+pub fn disassemble(memory: &[i64]) -> Vec<Line> {
+    let mut lines: Vec<Line> = Vec::new();
+    let mut address = 0;
+
+    while address < memory.len() {
+        let instruction = Instruction::decode(memory, address)
+            .ok()
+            .filter(|instr| address + instr.opcode.instr_len() <= memory.len())
+            .filter(|instr| instr.encode()[0] == memory[address]);
+
+        match instruction {
+            Some(instr) => {
+                let len = instr.opcode.instr_len();
+                lines.push(Line {
+                    address,
+                    words: memory[address..address + len].to_vec(),
+                    kind: LineKind::Instruction(instr),
+                });
+                address += len;
+            }
+            None => {
+                match lines.last_mut() {
+                    Some(line)
+                        if line.kind == LineKind::Data && line.words.len() < MAX_DATA_WORDS =>
+                    {
+                        line.words.push(memory[address])
+                    }
+                    _ => lines.push(Line {
+                        address,
+                        words: vec![memory[address]],
+                        kind: LineKind::Data,
+                    }),
+                }
+                address += 1;
+            }
+        }
+    }
+
+    lines
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let words = self
+            .words
+            .iter()
+            .map(|w| w.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        write!(f, "{:>5}: {:<24} ", self.address, words)?;
+
+        match &self.kind {
+            LineKind::Instruction(instr) => write!(f, "{}", instr),
+            LineKind::Data => {
+                let values = self
+                    .words
+                    .iter()
+                    .map(|w| w.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "DATA {} ; does not decode", values)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn listing() {
+        let memory = [1001, 225, 1, 6, 4, 6, 99, 0, -1, 7, 1];
+        let listing: Vec<String> = disassemble(&memory)
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+
+        assert_eq!(
+            listing,
+            vec![
+                "    0: 1001,225,1,6             ADD [225], #1 -> [6]",
+                "    4: 4,6                      OUT [6]",
+                "    6: 99                       HALT",
+                "    7: 0,-1,7,1                 DATA 0, -1, 7, 1 ; does not decode",
+            ]
+        );
+    }
+
+    #[test]
+    fn data_lines_are_split() {
+        let memory = [0; 10];
+        let lines = disassemble(&memory);
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].address, MAX_DATA_WORDS);
+        assert_eq!(lines[1].words.len(), 2);
+    }
+}
@@ -0,0 +1,168 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// Everything that can go wrong while loading or running an Intcode program.
+///
+/// Runtime errors carry the address of the faulting instruction (`instr_ptr`) and its raw,
+/// undecoded instruction word.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IntcodeError {
+    UnknownOpCode {
+        instr_ptr: usize,
+        instruction: i64,
+    },
+    UnknownParameterMode {
+        instr_ptr: usize,
+        instruction: i64,
+        mode: i64,
+    },
+    NegativeAddress {
+        instr_ptr: usize,
+        instruction: i64,
+        address: i64,
+    },
+    AddressOutOfBounds {
+        instr_ptr: usize,
+        instruction: i64,
+        address: i64,
+    },
+    WriteToImmediate {
+        instr_ptr: usize,
+        instruction: i64,
+    },
+    InputExhausted {
+        instr_ptr: usize,
+        instruction: i64,
+    },
+    /// The program used up its step budget before this instruction.
+    StepLimitExceeded {
+        instr_ptr: usize,
+        instruction: i64,
+        limit: u64,
+    },
+    /// The program ran for longer than its time budget.
+    TimeLimitExceeded {
+        instr_ptr: usize,
+        instruction: i64,
+        limit: Duration,
+    },
+    /// This jump would return the program to a state it was already in, with no input or
+    /// output since, so it would never stop.
+    InfiniteLoop {
+        instr_ptr: usize,
+        instruction: i64,
+    },
+    /// An addition, multiplication or relative base adjustment overflowed an `i64`.
+    Overflow {
+        instr_ptr: usize,
+        instruction: i64,
+    },
+    /// The program text contained something other than a comma-separated list of integers.
+    /// `position` is the index of the offending value in the list.
+    MalformedProgram {
+        position: usize,
+        token: String,
+    },
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntcodeError::UnknownOpCode {
+                instr_ptr,
+                instruction,
+            } => write!(
+                f,
+                "unknown opcode {} in instruction {} at address {}",
+                instruction % 100,
+                instruction,
+                instr_ptr
+            ),
+            IntcodeError::UnknownParameterMode {
+                instr_ptr,
+                instruction,
+                mode,
+            } => write!(
+                f,
+                "unknown parameter mode {} in instruction {} at address {}",
+                mode, instruction, instr_ptr
+            ),
+            IntcodeError::NegativeAddress {
+                instr_ptr,
+                instruction,
+                address,
+            } => write!(
+                f,
+                "negative address {} used by instruction {} at address {}",
+                address, instruction, instr_ptr
+            ),
+            IntcodeError::AddressOutOfBounds {
+                instr_ptr,
+                instruction,
+                address,
+            } => write!(
+                f,
+                "out of bounds address {} used by instruction {} at address {}",
+                address, instruction, instr_ptr
+            ),
+            IntcodeError::WriteToImmediate {
+                instr_ptr,
+                instruction,
+            } => write!(
+                f,
+                "instruction {} at address {} writes through an immediate parameter",
+                instruction, instr_ptr
+            ),
+            IntcodeError::InputExhausted {
+                instr_ptr,
+                instruction,
+            } => write!(
+                f,
+                "instruction {} at address {} needs input but none is available",
+                instruction, instr_ptr
+            ),
+            IntcodeError::StepLimitExceeded {
+                instr_ptr,
+                instruction,
+                limit,
+            } => write!(
+                f,
+                "step limit of {} reached before instruction {} at address {}",
+                limit, instruction, instr_ptr
+            ),
+            IntcodeError::TimeLimitExceeded {
+                instr_ptr,
+                instruction,
+                limit,
+            } => write!(
+                f,
+                "time limit of {:?} reached before instruction {} at address {}",
+                limit, instruction, instr_ptr
+            ),
+            IntcodeError::InfiniteLoop {
+                instr_ptr,
+                instruction,
+            } => write!(
+                f,
+                "instruction {} at address {} jumps back to a state the program was already in",
+                instruction, instr_ptr
+            ),
+            IntcodeError::Overflow {
+                instr_ptr,
+                instruction,
+            } => write!(
+                f,
+                "arithmetic overflow in instruction {} at address {}",
+                instruction, instr_ptr
+            ),
+            IntcodeError::MalformedProgram { position, token } => write!(
+                f,
+                "malformed program: value {} ({:?}) is not an integer",
+                position, token
+            ),
+        }
+    }
+}
+
+impl Error for IntcodeError {}
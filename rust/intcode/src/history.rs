@@ -0,0 +1,89 @@
+use crate::MemoryWrite;
+
+/// What one executed instruction changed, so that it can be undone.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Undo {
+    pub(crate) instr_ptr: usize,
+    pub(crate) relative_base: i64,
+    /// The length of memory, which a write past the end grows.
+    pub(crate) memory_len: usize,
+    pub(crate) write: Option<MemoryWrite>,
+    /// The value read by an `Input` instruction.
+    pub(crate) input: Option<i64>,
+}
+
+/// The undo log kept by a `Program` with history enabled.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct History {
+    pub(crate) undo: Vec<Undo>,
+    /// Inputs handed back by stepping backwards, most recent last. They are read again before
+    /// any new input.
+    pub(crate) replay: Vec<i64>,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{MemoryWrite, Program, Queue, Status};
+
+    /// Reads two values, then writes their sum and their product to addresses 20 and 21.
+    const SOURCE: [i64; 22] = [
+        3, 18, 3, 19, 1, 18, 19, 20, 2, 18, 19, 21, 4, 20, 99, 0, 0, 0, 0, 0, 0, 0,
+    ];
+
+    #[test]
+    fn step_back_restores_state() {
+        let output = Queue::new();
+        let mut program = Program::new(SOURCE.to_vec())
+            .with_history()
+            .with_input(Queue::from(vec![3, 4]))
+            .with_output(output.clone());
+        program.run().unwrap();
+
+        let finished = program.memory().clone();
+        assert_eq!(program.history_len(), 5);
+        while program.step_back() {}
+
+        assert_eq!(program.instr_ptr(), 0);
+        assert_eq!(program.steps(), 0);
+        assert_eq!(program.memory().to_vec(), SOURCE.to_vec());
+
+        // The inputs are replayed, so running forwards again gives the same result.
+        assert_eq!(program.resume(), Ok(Status::Output(7)));
+        assert_eq!(program.memory(), &finished);
+        assert_eq!(output.drain(), vec![7, 7]);
+    }
+
+    #[test]
+    fn step_back_shrinks_memory() {
+        let mut program = Program::new(vec![1101, 1, 1, 10, 99]).with_history();
+        program.run().unwrap();
+        assert_eq!(program.memory().len(), 11);
+
+        while program.step_back() {}
+        assert_eq!(program.memory().to_vec(), vec![1101, 1, 1, 10, 99]);
+    }
+
+    #[test]
+    fn run_back_to_last_write() {
+        let mut program = Program::new(SOURCE.to_vec())
+            .with_history()
+            .with_input(Queue::from(vec![3, 4]))
+            .with_output(Queue::new());
+        program.run().unwrap();
+
+        assert_eq!(
+            program.run_back_to_write(20),
+            Some(MemoryWrite {
+                address: 20,
+                old: 0,
+                new: 7
+            })
+        );
+        assert_eq!(program.instr_ptr(), 4);
+        assert_eq!(program.read(21), 0);
+        assert_eq!(program.read(19), 4);
+
+        assert_eq!(program.run_back_to_write(21), None);
+        assert_eq!(program.history_len(), 0);
+    }
+}
@@ -0,0 +1,343 @@
+use crate::IntcodeError;
+use std::convert::TryFrom;
+use std::fmt;
+
+/// The most parameters any instruction takes.
+pub const MAX_PARAMETERS: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Instruction {
+    pub opcode: OpCode,
+    /// Slots past the opcode's parameter count are unused and left as `[0]`.
+    parameters: [Parameter; MAX_PARAMETERS],
+}
+
+impl Instruction {
+    /// Panics if `parameters` doesn't have exactly as many entries as `opcode` takes.
+    pub fn new(opcode: OpCode, parameters: &[Parameter]) -> Self {
+        assert_eq!(
+            parameters.len(),
+            opcode.num_params(),
+            "wrong number of parameters for {}",
+            opcode.mnemonic()
+        );
+
+        let mut instr = Instruction {
+            opcode,
+            parameters: [Parameter::new(0, ParameterMode::Position); MAX_PARAMETERS],
+        };
+        instr.parameters[..parameters.len()].copy_from_slice(parameters);
+        instr
+    }
+
+    pub fn parameters(&self) -> &[Parameter] {
+        &self.parameters[..self.opcode.num_params()]
+    }
+
+    /// The `i`th parameter, without slicing off the unused ones first.
+    pub(crate) fn parameter(&self, i: usize) -> Parameter {
+        self.parameters[i]
+    }
+
+    /// Decodes the instruction starting at `address`. Words past the end of `memory` read as
+    /// zero.
+    pub fn decode(memory: &[i64], address: usize) -> Result<Self, IntcodeError> {
+        Instruction::decode_with(|a| memory.get(a).copied().unwrap_or(0), address)
+    }
+
+    /// Decodes the instruction at `address`, fetching memory words with `read`.
+    pub fn decode_with<F>(read: F, address: usize) -> Result<Self, IntcodeError>
+    where
+        F: Fn(usize) -> i64,
+    {
+        let raw_instruction = read(address);
+        let opcode =
+            OpCode::try_from(raw_instruction % 100).map_err(|_| IntcodeError::UnknownOpCode {
+                instr_ptr: address,
+                instruction: raw_instruction,
+            })?;
+        let mut modes = raw_instruction / 100;
+
+        let mut parameters = [Parameter::new(0, ParameterMode::Position); MAX_PARAMETERS];
+        for (p, parameter) in parameters.iter_mut().enumerate().take(opcode.num_params()) {
+            let param_mode = ParameterMode::try_from(modes % 10).map_err(|mode| {
+                IntcodeError::UnknownParameterMode {
+                    instr_ptr: address,
+                    instruction: raw_instruction,
+                    mode,
+                }
+            })?;
+            modes /= 10;
+            *parameter = Parameter::new(read(address + p + 1), param_mode);
+        }
+
+        Ok(Instruction { opcode, parameters })
+    }
+
+    /// Encodes the instruction back into memory words. The inverse of `decode`.
+    pub fn encode(&self) -> Vec<i64> {
+        let modes = self
+            .parameters()
+            .iter()
+            .rev()
+            .fold(0, |acc, p| acc * 10 + i64::from(p.mode));
+
+        let mut words = vec![modes * 100 + i64::from(self.opcode)];
+        words.extend(self.parameters().iter().map(|p| p.value));
+        words
+    }
+}
+
+/// Formats the instruction as assembly, e.g. `ADD [225], #1 -> [6]`. The parameter an
+/// instruction writes to is set apart with `->`.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.opcode.mnemonic())?;
+
+        for (i, parameter) in self.parameters().iter().enumerate() {
+            let separator = if Some(i) == self.opcode.output_param() {
+                " ->"
+            } else if i == 0 {
+                ""
+            } else {
+                ","
+            };
+            write!(f, "{} {}", separator, parameter)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum OpCode {
+    Add,
+    Multiply,
+    Input,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjustRelativeBase,
+    Halt,
+}
+
+/// Fails with the unrecognised value.
+impl TryFrom<i64> for OpCode {
+    type Error = i64;
+
+    fn try_from(n: i64) -> Result<Self, Self::Error> {
+        match n {
+            1 => Ok(OpCode::Add),
+            2 => Ok(OpCode::Multiply),
+            3 => Ok(OpCode::Input),
+            4 => Ok(OpCode::Output),
+            5 => Ok(OpCode::JumpIfTrue),
+            6 => Ok(OpCode::JumpIfFalse),
+            7 => Ok(OpCode::LessThan),
+            8 => Ok(OpCode::Equals),
+            9 => Ok(OpCode::AdjustRelativeBase),
+            99 => Ok(OpCode::Halt),
+            _ => Err(n),
+        }
+    }
+}
+
+impl From<OpCode> for i64 {
+    fn from(opcode: OpCode) -> Self {
+        match opcode {
+            OpCode::Add => 1,
+            OpCode::Multiply => 2,
+            OpCode::Input => 3,
+            OpCode::Output => 4,
+            OpCode::JumpIfTrue => 5,
+            OpCode::JumpIfFalse => 6,
+            OpCode::LessThan => 7,
+            OpCode::Equals => 8,
+            OpCode::AdjustRelativeBase => 9,
+            OpCode::Halt => 99,
+        }
+    }
+}
+
+impl OpCode {
+    pub const ALL: [OpCode; 10] = [
+        OpCode::Add,
+        OpCode::Multiply,
+        OpCode::Input,
+        OpCode::Output,
+        OpCode::JumpIfTrue,
+        OpCode::JumpIfFalse,
+        OpCode::LessThan,
+        OpCode::Equals,
+        OpCode::AdjustRelativeBase,
+        OpCode::Halt,
+    ];
+
+    pub fn num_params(&self) -> usize {
+        match self {
+            OpCode::Add | OpCode::Multiply | OpCode::LessThan | OpCode::Equals => 3,
+            OpCode::Input | OpCode::Output | OpCode::AdjustRelativeBase => 1,
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse => 2,
+            OpCode::Halt => 0,
+        }
+    }
+
+    pub fn instr_len(&self) -> usize {
+        self.num_params() + 1
+    }
+
+    /// The index of the parameter this instruction writes its result to, if any.
+    pub fn output_param(&self) -> Option<usize> {
+        match self {
+            OpCode::Add | OpCode::Multiply | OpCode::LessThan | OpCode::Equals => Some(2),
+            OpCode::Input => Some(0),
+            _ => None,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            OpCode::Add => "ADD",
+            OpCode::Multiply => "MUL",
+            OpCode::Input => "IN",
+            OpCode::Output => "OUT",
+            OpCode::JumpIfTrue => "JT",
+            OpCode::JumpIfFalse => "JF",
+            OpCode::LessThan => "LT",
+            OpCode::Equals => "EQ",
+            OpCode::AdjustRelativeBase => "ARB",
+            OpCode::Halt => "HALT",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Parameter {
+    pub value: i64,
+    pub mode: ParameterMode,
+}
+
+impl Parameter {
+    pub fn new(value: i64, mode: ParameterMode) -> Self {
+        Parameter { value, mode }
+    }
+}
+
+/// Position parameters are written as `[address]`, immediates as `#value` and relative
+/// parameters as `[rb+offset]`.
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mode {
+            ParameterMode::Position => write!(f, "[{}]", self.value),
+            ParameterMode::Immediate => write!(f, "#{}", self.value),
+            ParameterMode::Relative => write!(f, "[rb{:+}]", self.value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ParameterMode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+/// Fails with the unrecognised value.
+impl TryFrom<i64> for ParameterMode {
+    type Error = i64;
+
+    fn try_from(n: i64) -> Result<Self, Self::Error> {
+        match n {
+            0 => Ok(ParameterMode::Position),
+            1 => Ok(ParameterMode::Immediate),
+            2 => Ok(ParameterMode::Relative),
+            _ => Err(n),
+        }
+    }
+}
+
+impl From<ParameterMode> for i64 {
+    fn from(mode: ParameterMode) -> Self {
+        match mode {
+            ParameterMode::Position => 0,
+            ParameterMode::Immediate => 1,
+            ParameterMode::Relative => 2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn opcode_try_from() {
+        assert_eq!(OpCode::try_from(1), Ok(OpCode::Add));
+        assert_eq!(OpCode::try_from(9), Ok(OpCode::AdjustRelativeBase));
+        assert_eq!(OpCode::try_from(99), Ok(OpCode::Halt));
+        assert_eq!(OpCode::try_from(42), Err(42));
+    }
+
+    #[test]
+    fn decode() {
+        let memory = [1002, 4, 3, 4, 33];
+
+        assert_eq!(
+            Instruction::decode(&memory, 0),
+            Ok(Instruction::new(
+                OpCode::Multiply,
+                &[
+                    Parameter::new(4, ParameterMode::Position),
+                    Parameter::new(3, ParameterMode::Immediate),
+                    Parameter::new(4, ParameterMode::Position),
+                ]
+            ))
+        );
+        assert!(Instruction::decode(&memory, 4).is_err());
+    }
+
+    #[test]
+    fn encode() {
+        let memory = [1002, 4, 3, 4, 21107, -1, 8, 3, 99];
+
+        assert_eq!(
+            Instruction::decode(&memory, 0).unwrap().encode(),
+            &memory[0..4]
+        );
+        assert_eq!(
+            Instruction::decode(&memory, 4).unwrap().encode(),
+            &memory[4..8]
+        );
+        assert_eq!(
+            Instruction::decode(&memory, 8).unwrap().encode(),
+            &memory[8..]
+        );
+    }
+
+    #[test]
+    fn display() {
+        let memory = [1001, 225, 1, 6, 204, -3, 3, 7, 99];
+
+        assert_eq!(
+            Instruction::decode(&memory, 0).unwrap().to_string(),
+            "ADD [225], #1 -> [6]"
+        );
+        assert_eq!(
+            Instruction::decode(&memory, 4).unwrap().to_string(),
+            "OUT [rb-3]"
+        );
+        assert_eq!(
+            Instruction::decode(&memory, 6).unwrap().to_string(),
+            "IN -> [7]"
+        );
+        assert_eq!(Instruction::decode(&memory, 8).unwrap().to_string(), "HALT");
+    }
+
+    #[test]
+    fn parameter_mode_try_from() {
+        assert_eq!(ParameterMode::try_from(2), Ok(ParameterMode::Relative));
+        assert_eq!(ParameterMode::try_from(3), Err(3));
+    }
+}
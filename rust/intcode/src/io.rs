@@ -0,0 +1,177 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+use std::sync::{Arc, Mutex};
+
+/// Supplies values to `Input` instructions.
+pub trait InputSource: Send {
+    /// Returns the next input value, or `None` if no input is available.
+    fn next_input(&mut self) -> Option<i64>;
+
+    /// Values that are waiting to be read, for sources that buffer them. Used when taking
+    /// snapshots.
+    fn pending(&self) -> Vec<i64> {
+        Vec::new()
+    }
+}
+
+/// Receives values produced by `Output` instructions.
+pub trait OutputSink: Send {
+    fn send_output(&mut self, value: i64);
+
+    /// Values that were sent but not yet consumed, for sinks that buffer them. Used when taking
+    /// snapshots.
+    fn pending(&self) -> Vec<i64> {
+        Vec::new()
+    }
+}
+
+/// Reads one integer per line from stdin. End of file means no more input.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StdinSource;
+
+impl InputSource for StdinSource {
+    fn next_input(&mut self) -> Option<i64> {
+        let mut line = String::new();
+        let bytes_read = io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .expect("Unable to read input");
+
+        if bytes_read == 0 {
+            return None;
+        }
+
+        Some(line.trim().parse().expect("Input is not an integer"))
+    }
+}
+
+/// Prints each output value on its own line.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StdoutSink;
+
+impl OutputSink for StdoutSink {
+    fn send_output(&mut self, value: i64) {
+        println!("{}", value);
+    }
+}
+
+/// An in-memory FIFO queue of values.
+///
+/// Clones share the same underlying queue, so one handle can be given to a `Program` while
+/// another is kept to push inputs or collect outputs.
+#[derive(Clone, Debug, Default)]
+pub struct Queue {
+    values: Arc<Mutex<VecDeque<i64>>>,
+}
+
+impl Queue {
+    pub fn new() -> Self {
+        Queue::default()
+    }
+
+    pub fn push(&self, value: i64) {
+        self.values.lock().unwrap().push_back(value);
+    }
+
+    pub fn pop(&self) -> Option<i64> {
+        self.values.lock().unwrap().pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns every queued value, oldest first, without removing them.
+    pub fn values(&self) -> Vec<i64> {
+        self.values.lock().unwrap().iter().copied().collect()
+    }
+
+    /// Removes and returns every queued value, oldest first.
+    pub fn drain(&self) -> Vec<i64> {
+        self.values.lock().unwrap().drain(..).collect()
+    }
+}
+
+impl From<Vec<i64>> for Queue {
+    fn from(values: Vec<i64>) -> Self {
+        Queue {
+            values: Arc::new(Mutex::new(values.into())),
+        }
+    }
+}
+
+impl InputSource for Queue {
+    fn next_input(&mut self) -> Option<i64> {
+        self.pop()
+    }
+
+    fn pending(&self) -> Vec<i64> {
+        self.values()
+    }
+}
+
+impl OutputSink for Queue {
+    fn send_output(&mut self, value: i64) {
+        self.push(value);
+    }
+
+    fn pending(&self) -> Vec<i64> {
+        self.values()
+    }
+}
+
+impl<F> InputSource for F
+where
+    F: FnMut() -> Option<i64> + Send,
+{
+    fn next_input(&mut self) -> Option<i64> {
+        self()
+    }
+}
+
+impl<F> OutputSink for F
+where
+    F: FnMut(i64) + Send,
+{
+    fn send_output(&mut self, value: i64) {
+        self(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn queue_is_shared_between_clones() {
+        let queue = Queue::from(vec![1, 2]);
+        let mut source = queue.clone();
+
+        queue.push(3);
+
+        assert_eq!(source.next_input(), Some(1));
+        assert_eq!(queue.drain(), vec![2, 3]);
+        assert_eq!(source.next_input(), None);
+    }
+
+    #[test]
+    fn closures() {
+        let mut count = 0;
+        let mut source = move || {
+            count += 1;
+            Some(count)
+        };
+        assert_eq!(source.next_input(), Some(1));
+        assert_eq!(source.next_input(), Some(2));
+
+        let outputs = Queue::new();
+        let collected = outputs.clone();
+        let mut sink = move |value| collected.push(value * 2);
+        sink.send_output(21);
+        assert_eq!(outputs.drain(), vec![42]);
+    }
+}
@@ -0,0 +1,46 @@
+//! An interpreter for the Intcode computer used throughout Advent of Code 2019.
+
+pub use crate::amplifier::{max_thruster_signal, AmplifierChain};
+pub use crate::ascii::{AsciiOutput, AsciiProgram};
+pub use crate::asm::{assemble, AsmError};
+pub use crate::cfg::{control_flow_graph, BasicBlock, BlockExit, ControlFlowGraph, Edge, EdgeKind};
+pub use crate::debugger::Debugger;
+pub use crate::disasm::{disassemble, Line, LineKind};
+pub use crate::error::IntcodeError;
+pub use crate::instruction::{Instruction, OpCode, Parameter, ParameterMode, MAX_PARAMETERS};
+pub use crate::io::{InputSource, OutputSink, Queue, StdinSource, StdoutSink};
+pub use crate::memory::Memory;
+pub use crate::network::{
+    first_nat_packet, first_repeated_nat_y, Network, NetworkEvent, Packet, Scheduler, NAT_ADDRESS,
+};
+pub use crate::observer::{Event, Observer};
+pub use crate::profile::{JumpCounts, Profile, Profiler};
+pub use crate::program::{Program, Status};
+pub use crate::search::GoalSearch;
+pub use crate::snapshot::{Snapshot, SnapshotError};
+pub use crate::threaded::Backend;
+pub use crate::trace::{JsonLinesTracer, MemoryWrite, TraceRecord, Tracer};
+pub use crate::transpile::transpile;
+
+mod amplifier;
+mod ascii;
+mod asm;
+mod cache;
+mod cfg;
+mod debugger;
+mod disasm;
+mod error;
+mod history;
+mod instruction;
+mod io;
+mod loops;
+mod memory;
+mod network;
+mod observer;
+mod profile;
+mod program;
+mod search;
+mod snapshot;
+mod threaded;
+mod trace;
+mod transpile;
@@ -0,0 +1,114 @@
+use crate::Memory;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+/// The states a program with loop detection on has been in at each backward jump since its
+/// last input or output. A state is memory, the jump target and the relative base.
+///
+/// Only a hash of each state is kept, so jumping doesn't copy memory. The hash of memory is
+/// updated by every write rather than recomputed from scratch. A state is only stored in full
+/// once its hash has come up before, and only reported once that full state comes up again, so
+/// a collision can't be mistaken for a loop. Loops are caught on their second pass instead of
+/// their first.
+#[derive(Debug, Default)]
+pub(crate) struct SeenStates {
+    memory_hash: u64,
+    hashes: HashSet<u64>,
+    /// States whose hash was seen more than once, by hash.
+    repeated: HashMap<u64, (Memory, usize, i64)>,
+}
+
+impl SeenStates {
+    pub(crate) fn new(memory: &Memory) -> Self {
+        let memory_hash = memory
+            .iter()
+            .enumerate()
+            .fold(0, |hash, (address, value)| hash ^ word_hash(address, value));
+
+        SeenStates {
+            memory_hash,
+            ..SeenStates::default()
+        }
+    }
+
+    /// Starts over with no states seen, for a fork of the program with the same memory.
+    pub(crate) fn fork(&self) -> Self {
+        SeenStates {
+            memory_hash: self.memory_hash,
+            ..SeenStates::default()
+        }
+    }
+
+    /// Updates the hash of memory for a write of `new` over `old` at `address`.
+    pub(crate) fn write(&mut self, address: usize, old: i64, new: i64) {
+        self.memory_hash ^= word_hash(address, old) ^ word_hash(address, new);
+    }
+
+    /// Records a state, returning true if the program was already in it.
+    pub(crate) fn repeats(&mut self, memory: &Memory, target: usize, relative_base: i64) -> bool {
+        let mut hasher = DefaultHasher::new();
+        (self.memory_hash, memory.len(), target, relative_base).hash(&mut hasher);
+        let hash = hasher.finish();
+
+        if self.hashes.insert(hash) {
+            return false;
+        }
+        match self.repeated.get(&hash) {
+            Some((m, t, r)) if (m, *t, *r) == (memory, target, relative_base) => true,
+            _ => {
+                let state = (memory.clone(), target, relative_base);
+                self.repeated.insert(hash, state);
+                false
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.hashes.clear();
+        self.repeated.clear();
+    }
+}
+
+/// Mixes a word of memory and its address. Zero words count for nothing, so memory growing or
+/// shrinking over zeroes doesn't change its hash.
+fn word_hash(address: usize, value: i64) -> u64 {
+    if value == 0 {
+        return 0;
+    }
+
+    // The finalizer of SplitMix64, which is much cheaper than hashing with `DefaultHasher`
+    // and plenty for values that are compared in full before anything is reported.
+    let mut x = (address as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ value as u64;
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn repeats_on_second_pass() {
+        let memory = Memory::from(vec![1, 2, 3]);
+        let mut seen = SeenStates::new(&memory);
+
+        assert!(!seen.repeats(&memory, 0, 0));
+        assert!(!seen.repeats(&memory, 1, 0));
+        assert!(!seen.repeats(&memory, 0, 0));
+        assert!(seen.repeats(&memory, 0, 0));
+    }
+
+    #[test]
+    fn writes_update_the_hash() {
+        let mut memory = Memory::from(vec![1, 2, 3]);
+        let mut seen = SeenStates::new(&memory);
+
+        memory.write(5, 7);
+        seen.write(5, 0, 7);
+        memory.write(1, 0);
+        seen.write(1, 2, 0);
+        assert_eq!(seen.memory_hash, SeenStates::new(&memory).memory_hash);
+    }
+}
@@ -0,0 +1,159 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Index;
+use std::sync::Arc;
+
+/// The number of words in each page.
+const PAGE_SIZE: usize = 512;
+
+type Page = [i64; PAGE_SIZE];
+
+/// A program's memory, stored as shared fixed-size pages.
+///
+/// Cloning only copies the page table. Pages stay shared between clones until one of them
+/// writes to a page, at which point that page alone is copied.
+#[derive(Clone, Default, Eq, PartialEq)]
+pub struct Memory {
+    pages: Vec<Arc<Page>>,
+    len: usize,
+}
+
+impl Memory {
+    /// Addresses at or past the end read as zero.
+    pub fn read(&self, address: usize) -> i64 {
+        match self.pages.get(address / PAGE_SIZE) {
+            Some(page) => page[address % PAGE_SIZE],
+            None => 0,
+        }
+    }
+
+    /// Writing past the end grows memory, filling the gap with zeroes.
+    pub fn write(&mut self, address: usize, value: i64) {
+        let page = address / PAGE_SIZE;
+        if page >= self.pages.len() {
+            self.pages
+                .resize_with(page + 1, || Arc::new([0; PAGE_SIZE]));
+        }
+
+        Arc::make_mut(&mut self.pages[page])[address % PAGE_SIZE] = value;
+        self.len = self.len.max(address + 1);
+    }
+
+    /// Shrinks memory to `len` words, as if nothing past them had been written. Does nothing
+    /// if memory is already that short.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+
+        self.pages.truncate(len.div_ceil(PAGE_SIZE));
+        if let Some(page) = self.pages.get_mut(len / PAGE_SIZE) {
+            Arc::make_mut(page)[len % PAGE_SIZE..]
+                .iter_mut()
+                .for_each(|v| *v = 0);
+        }
+        self.len = len;
+    }
+
+    /// One past the highest address that was loaded or written.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = i64> + '_ {
+        self.pages
+            .iter()
+            .flat_map(|page| page.iter().copied())
+            .take(self.len)
+    }
+
+    pub fn to_vec(&self) -> Vec<i64> {
+        self.iter().collect()
+    }
+}
+
+impl From<Vec<i64>> for Memory {
+    fn from(values: Vec<i64>) -> Self {
+        let pages = values
+            .chunks(PAGE_SIZE)
+            .map(|chunk| {
+                let mut page = [0; PAGE_SIZE];
+                page[..chunk.len()].copy_from_slice(chunk);
+                Arc::new(page)
+            })
+            .collect();
+
+        Memory {
+            pages,
+            len: values.len(),
+        }
+    }
+}
+
+impl Index<usize> for Memory {
+    type Output = i64;
+
+    fn index(&self, address: usize) -> &i64 {
+        match self.pages.get(address / PAGE_SIZE) {
+            Some(page) => &page[address % PAGE_SIZE],
+            None => &0,
+        }
+    }
+}
+
+impl Hash for Memory {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len.hash(state);
+        for value in self.iter() {
+            value.hash(state);
+        }
+    }
+}
+
+impl fmt::Debug for Memory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn read_and_write() {
+        let mut memory = Memory::from(vec![1, 2, 3]);
+
+        assert_eq!(memory.read(2), 3);
+        assert_eq!(memory.read(5000), 0);
+
+        memory.write(1500, 7);
+        assert_eq!(memory.len(), 1501);
+        assert_eq!(memory[1500], 7);
+        assert_eq!(memory.read(1499), 0);
+        assert_eq!(&memory.to_vec()[..4], &[1, 2, 3, 0]);
+
+        memory.truncate(2);
+        assert_eq!(memory, Memory::from(vec![1, 2]));
+        assert_eq!(memory.read(1500), 0);
+    }
+
+    #[test]
+    fn clones_share_untouched_pages() {
+        let original = Memory::from((0..4 * PAGE_SIZE as i64).collect::<Vec<_>>());
+        let mut copy = original.clone();
+        copy.write(PAGE_SIZE + 1, -1);
+
+        assert_eq!(original.read(PAGE_SIZE + 1), PAGE_SIZE as i64 + 1);
+        assert_eq!(copy.read(PAGE_SIZE + 1), -1);
+
+        let shared: Vec<bool> = (0..4)
+            .map(|i| Arc::ptr_eq(&original.pages[i], &copy.pages[i]))
+            .collect();
+        assert_eq!(shared, vec![true, false, true, true]);
+    }
+}
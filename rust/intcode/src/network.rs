@@ -0,0 +1,441 @@
+use crate::{IntcodeError, Program, Queue, Status};
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// The address packets are sent to in order to reach the NAT.
+pub const NAT_ADDRESS: usize = 255;
+
+/// The most instructions a node may execute in one round before yielding.
+const ROUND_STEP_LIMIT: u64 = 100_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Packet {
+    pub destination: usize,
+    pub x: i64,
+    pub y: i64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NetworkEvent {
+    /// A node sent a packet to the NAT.
+    PacketToNat(Packet),
+    /// The network was idle, so the NAT sent its last packet to address 0.
+    NatDelivery(Packet),
+}
+
+/// How nodes are run within a round.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scheduler {
+    /// Every node runs in turn on the calling thread.
+    Cooperative,
+    /// Nodes are split between this many threads, which are started by `with_scheduler` and
+    /// kept until the network is dropped.
+    Threaded(usize),
+}
+
+struct Node {
+    program: Program,
+    input: Queue,
+    /// Output values that don't yet make up a whole packet.
+    partial: Vec<i64>,
+    halted: bool,
+    /// Whether the node ended its last turn waiting on an empty queue.
+    idle: bool,
+}
+
+impl Node {
+    /// Runs the node until it asks for input twice with nothing queued, halts, or uses up its
+    /// step budget. The first time the queue is empty, the node is given `-1`.
+    fn take_turn(&mut self) -> Result<Vec<Packet>, IntcodeError> {
+        let mut packets = Vec::new();
+        let mut given_empty = false;
+        let step_limit = self.program.steps() + ROUND_STEP_LIMIT;
+        self.idle = false;
+
+        while !self.halted && self.program.steps() < step_limit {
+            // Step by step, since `resume` wouldn't return from a node that never does I/O.
+            match self.program.step()? {
+                None => {}
+                Some(Status::Output(value)) => {
+                    self.partial.push(value);
+                    if let [destination, x, y] = self.partial[..] {
+                        packets.push(Packet {
+                            destination: destination as usize,
+                            x,
+                            y,
+                        });
+                        self.partial.clear();
+                    }
+                }
+                Some(Status::NeedsInput) if !given_empty => {
+                    self.input.push(-1);
+                    given_empty = true;
+                }
+                Some(Status::NeedsInput) => {
+                    self.idle = true;
+                    break;
+                }
+                Some(Status::Halted) => self.halted = true,
+            }
+        }
+
+        Ok(packets)
+    }
+}
+
+/// The packets each node in a batch sent during its turn, or the first error.
+type TurnResult = Result<Vec<Vec<Packet>>, IntcodeError>;
+
+/// Worker threads that take turns for batches of nodes, one round at a time.
+///
+/// Batches are moved to the workers and handed back along with their results, so the nodes
+/// are only ever touched by one thread at a time.
+struct WorkerPool {
+    threads: usize,
+    /// `None` once the pool is being dropped, which tells the workers to stop.
+    batches: Option<Sender<(usize, Vec<Node>)>>,
+    results: Receiver<(usize, Vec<Node>, thread::Result<TurnResult>)>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    fn new(threads: usize) -> Self {
+        let (batches, batch_receiver) = mpsc::channel::<(usize, Vec<Node>)>();
+        let (result_sender, results) = mpsc::channel();
+        let batch_receiver = Arc::new(Mutex::new(batch_receiver));
+
+        let workers = (0..threads)
+            .map(|_| {
+                let (batch_receiver, result_sender) =
+                    (batch_receiver.clone(), result_sender.clone());
+                thread::spawn(move || loop {
+                    let batch = batch_receiver.lock().unwrap().recv();
+                    let (index, mut nodes) = match batch {
+                        Ok(batch) => batch,
+                        Err(_) => break,
+                    };
+                    // A panic is handed back to the calling thread rather than losing the batch.
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                        nodes.iter_mut().map(Node::take_turn).collect()
+                    }));
+                    if result_sender.send((index, nodes, result)).is_err() {
+                        break;
+                    }
+                })
+            })
+            .collect();
+
+        WorkerPool {
+            threads,
+            batches: Some(batches),
+            results,
+            workers,
+        }
+    }
+
+    /// Gives every node a turn, split into one batch per thread, and returns the packets each
+    /// one sent in node order.
+    fn take_turns(&self, nodes: &mut Vec<Node>) -> Result<Vec<Vec<Packet>>, IntcodeError> {
+        let batch_size = nodes.len().div_ceil(self.threads).max(1);
+        let mut remaining = mem::take(nodes);
+        let mut count = 0;
+        while !remaining.is_empty() {
+            let rest = remaining.split_off(batch_size.min(remaining.len()));
+            let batches = self.batches.as_ref().expect("Worker pool is shut down");
+            batches
+                .send((count, remaining))
+                .expect("Network thread stopped");
+            remaining = rest;
+            count += 1;
+        }
+
+        let mut finished: Vec<_> = (0..count)
+            .map(|_| self.results.recv().expect("Network thread stopped"))
+            .collect();
+        finished.sort_unstable_by_key(|&(index, _, _)| index);
+
+        let mut sent = Vec::new();
+        let mut error = None;
+        for (_, batch, result) in finished {
+            nodes.extend(batch);
+            match result.unwrap_or_else(|e| panic::resume_unwind(e)) {
+                Ok(packets) => sent.extend(packets),
+                Err(e) => error = error.or(Some(e)),
+            }
+        }
+
+        match error {
+            Some(e) => Err(e),
+            None => Ok(sent),
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        self.batches = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// A cluster of networked programs that exchange `(destination, x, y)` packets.
+///
+/// Execution proceeds in rounds. Every node takes a turn, and the packets sent during the round
+/// are delivered, in node order, once all turns are over. Nodes never see packets sent in the
+/// same round, so the results don't depend on how turns are scheduled.
+pub struct Network {
+    nodes: Vec<Node>,
+    /// The threads nodes take their turns on, unless they all run on the calling thread.
+    pool: Option<WorkerPool>,
+    /// The last packet sent to the NAT.
+    nat: Option<Packet>,
+}
+
+impl Network {
+    /// Boots `size` copies of the program, each given its network address as its first input.
+    pub fn new(memory: &[i64], size: usize) -> Self {
+        let nodes = (0..size)
+            .map(|address| {
+                let input = Queue::from(vec![address as i64]);
+                Node {
+                    program: Program::new(memory.to_vec())
+                        .with_input(input.clone())
+                        .with_output(|_| {}),
+                    input,
+                    partial: Vec::new(),
+                    halted: false,
+                    idle: false,
+                }
+            })
+            .collect();
+
+        Network {
+            nodes,
+            pool: None,
+            nat: None,
+        }
+    }
+
+    pub fn with_scheduler(mut self, scheduler: Scheduler) -> Self {
+        self.pool = match scheduler {
+            Scheduler::Cooperative => None,
+            Scheduler::Threaded(threads) => Some(WorkerPool::new(threads.max(1))),
+        };
+        self
+    }
+
+    /// Runs a single round and returns what the NAT saw or did.
+    ///
+    /// Packets addressed to neither a node nor the NAT are dropped.
+    pub fn round(&mut self) -> Result<Vec<NetworkEvent>, IntcodeError> {
+        let sent = match &self.pool {
+            None => self
+                .nodes
+                .iter_mut()
+                .map(Node::take_turn)
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(pool) => pool.take_turns(&mut self.nodes)?,
+        };
+
+        let mut events = Vec::new();
+        for packet in sent.iter().flatten() {
+            if packet.destination == NAT_ADDRESS {
+                self.nat = Some(*packet);
+                events.push(NetworkEvent::PacketToNat(*packet));
+            } else if let Some(node) = self.nodes.get(packet.destination) {
+                node.input.push(packet.x);
+                node.input.push(packet.y);
+            }
+        }
+
+        let idle = sent.iter().all(|packets| packets.is_empty())
+            && self
+                .nodes
+                .iter()
+                .all(|node| node.halted || (node.idle && node.input.is_empty()));
+        if idle {
+            if let (Some(packet), Some(node)) = (self.nat, self.nodes.first()) {
+                node.input.push(packet.x);
+                node.input.push(packet.y);
+                events.push(NetworkEvent::NatDelivery(packet));
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// Runs rounds until `stop` returns true for an event, and returns that event.
+    ///
+    /// Returns `None` if the network can make no further progress: every node has halted, or
+    /// the network is idle and the NAT has nothing to send.
+    pub fn run_until<F>(&mut self, mut stop: F) -> Result<Option<NetworkEvent>, IntcodeError>
+    where
+        F: FnMut(&NetworkEvent) -> bool,
+    {
+        loop {
+            let events = self.round()?;
+            if let Some(event) = events.iter().find(|event| stop(event)) {
+                return Ok(Some(*event));
+            }
+
+            let stuck = self.nodes.iter().all(|node| node.halted)
+                || (self.nat.is_none()
+                    && self
+                        .nodes
+                        .iter()
+                        .all(|node| node.halted || (node.idle && node.input.is_empty())));
+            if stuck {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+/// Returns the first packet sent to the NAT.
+pub fn first_nat_packet(
+    memory: &[i64],
+    size: usize,
+    scheduler: Scheduler,
+) -> Result<Option<Packet>, IntcodeError> {
+    let mut network = Network::new(memory, size).with_scheduler(scheduler);
+    let event = network.run_until(|event| matches!(event, NetworkEvent::PacketToNat(_)))?;
+
+    Ok(event.map(|event| match event {
+        NetworkEvent::PacketToNat(packet) | NetworkEvent::NatDelivery(packet) => packet,
+    }))
+}
+
+/// Returns the first `y` value the NAT delivers to address 0 twice in a row.
+pub fn first_repeated_nat_y(
+    memory: &[i64],
+    size: usize,
+    scheduler: Scheduler,
+) -> Result<Option<i64>, IntcodeError> {
+    let mut network = Network::new(memory, size).with_scheduler(scheduler);
+    let mut last_y = None;
+
+    let event = network.run_until(|event| match event {
+        NetworkEvent::NatDelivery(packet) => {
+            let repeated = last_y == Some(packet.y);
+            last_y = Some(packet.y);
+            repeated
+        }
+        NetworkEvent::PacketToNat(_) => false,
+    })?;
+
+    Ok(event.map(|_| last_y.unwrap()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::assemble;
+
+    /// Node 0 starts a packet around the ring. Every node forwards what it receives to the next
+    /// address with `x` incremented, and the last node forwards to the NAT. Node 0 doubles `y`
+    /// until it reaches 400.
+    const RING: &str = "
+                IN -> [addr]
+                JT [addr], #loop
+                OUT #1
+                OUT #0
+                OUT #100
+        loop:   IN -> [x]
+                EQ [x], #-1 -> [t]
+                JT [t], #loop
+                IN -> [y]
+                JT [addr], #forward
+                LT [y], #400 -> [t]
+                JF [t], #forward
+                MUL [y], #2 -> [y]
+        forward: ADD [addr], #1 -> [dest]
+                EQ [dest], #SIZE -> [t]
+                JF [t], #send
+                ADD #255, #0 -> [dest]
+        send:   OUT [dest]
+                ADD [x], #1 -> [x]
+                OUT [x]
+                OUT [y]
+                JT #1, #loop
+                HALT
+        addr:   data 0
+        x:      data 0
+        y:      data 0
+        t:      data 0
+        dest:   data 0
+    ";
+
+    fn ring(size: usize) -> Vec<i64> {
+        assemble(&RING.replace("SIZE", &size.to_string())).unwrap()
+    }
+
+    #[test]
+    fn first_packet_to_nat() {
+        let memory = ring(10);
+
+        assert_eq!(
+            first_nat_packet(&memory, 10, Scheduler::Cooperative),
+            Ok(Some(Packet {
+                destination: NAT_ADDRESS,
+                x: 9,
+                y: 100
+            }))
+        );
+    }
+
+    #[test]
+    fn nat_repeats() {
+        let memory = ring(50);
+
+        assert_eq!(
+            first_repeated_nat_y(&memory, 50, Scheduler::Cooperative),
+            Ok(Some(400))
+        );
+    }
+
+    #[test]
+    fn schedulers_agree() {
+        let memory = ring(50);
+        let mut events = Vec::new();
+
+        for &scheduler in &[
+            Scheduler::Cooperative,
+            Scheduler::Threaded(1),
+            Scheduler::Threaded(4),
+            Scheduler::Threaded(64),
+        ] {
+            let mut network = Network::new(&memory, 50).with_scheduler(scheduler);
+            let mut seen = Vec::new();
+            for _ in 0..200 {
+                seen.extend(network.round().unwrap());
+            }
+            events.push(seen);
+        }
+
+        assert!(!events[0].is_empty());
+        assert!(events.iter().all(|e| *e == events[0]));
+    }
+
+    #[test]
+    fn step_budget_per_turn() {
+        // Reads its address, then spins forever without any I/O.
+        let memory = assemble(
+            "       IN -> [address]
+             spin:  JT #1, #spin
+             address: data 0",
+        )
+        .unwrap();
+        let mut network = Network::new(&memory, 2);
+
+        assert_eq!(network.round(), Ok(vec![]));
+        assert_eq!(network.round(), Ok(vec![]));
+        for node in &network.nodes {
+            assert_eq!(node.program.steps(), 2 * ROUND_STEP_LIMIT);
+        }
+    }
+}
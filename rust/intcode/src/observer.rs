@@ -0,0 +1,130 @@
+use crate::MemoryWrite;
+use std::ops::{Bound, Range, RangeBounds};
+
+/// Something an instruction did that observers can watch for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    /// An instruction read a parameter from memory. Immediate parameters aren't reads.
+    Read {
+        instr_ptr: usize,
+        address: usize,
+        value: i64,
+    },
+    /// An instruction wrote to memory.
+    Write {
+        instr_ptr: usize,
+        write: MemoryWrite,
+    },
+    /// A jump instruction was taken.
+    Jump { instr_ptr: usize, target: usize },
+}
+
+impl Event {
+    /// The address the event is about: the address read or written, or the jump target.
+    pub fn address(&self) -> usize {
+        match *self {
+            Event::Read { address, .. } => address,
+            Event::Write { write, .. } => write.address,
+            Event::Jump { target, .. } => target,
+        }
+    }
+}
+
+/// Receives the events of a `Program` whose address falls in the range it was registered
+/// with.
+pub trait Observer: Send {
+    fn observe(&mut self, event: &Event);
+}
+
+impl<F> Observer for F
+where
+    F: FnMut(&Event) + Send,
+{
+    fn observe(&mut self, event: &Event) {
+        self(event)
+    }
+}
+
+/// An observer along with the addresses it watches.
+pub(crate) struct Watch {
+    addresses: Range<usize>,
+    observer: Box<dyn Observer>,
+}
+
+impl Watch {
+    pub(crate) fn new(addresses: impl RangeBounds<usize>, observer: Box<dyn Observer>) -> Self {
+        let start = match addresses.start_bound() {
+            Bound::Included(&a) => a,
+            Bound::Excluded(&a) => a.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match addresses.end_bound() {
+            Bound::Included(&a) => a.saturating_add(1),
+            Bound::Excluded(&a) => a,
+            Bound::Unbounded => usize::MAX,
+        };
+
+        Watch {
+            addresses: start..end,
+            observer,
+        }
+    }
+
+    pub(crate) fn notify(&mut self, event: &Event) {
+        if self.addresses.contains(&event.address()) {
+            self.observer.observe(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Program;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn watched_ranges() {
+        let watched = Arc::new(Mutex::new(Vec::new()));
+        let jumps = Arc::new(Mutex::new(Vec::new()));
+        let (w, j) = (watched.clone(), jumps.clone());
+
+        // Counts address 9 down from 2, then halts.
+        let mut program = Program::new(vec![1001, 9, -1, 9, 1005, 9, 0, 99, 0, 2])
+            .with_observer(9..10, move |e: &Event| w.lock().unwrap().push(*e))
+            .with_observer(..=0, move |e: &Event| j.lock().unwrap().push(*e));
+        program.run().unwrap();
+
+        let read = |instr_ptr, value| Event::Read {
+            instr_ptr,
+            address: 9,
+            value,
+        };
+        let write = |old, new| Event::Write {
+            instr_ptr: 0,
+            write: MemoryWrite {
+                address: 9,
+                old,
+                new,
+            },
+        };
+        assert_eq!(
+            *watched.lock().unwrap(),
+            vec![
+                read(0, 2),
+                write(2, 1),
+                read(4, 1),
+                read(0, 1),
+                write(1, 0),
+                read(4, 0),
+            ]
+        );
+        assert_eq!(
+            *jumps.lock().unwrap(),
+            vec![Event::Jump {
+                instr_ptr: 4,
+                target: 0
+            }]
+        );
+    }
+}
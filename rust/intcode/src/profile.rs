@@ -0,0 +1,271 @@
+use crate::{OpCode, ParameterMode, TraceRecord, Tracer};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+
+/// How often a conditional jump at one address was taken.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct JumpCounts {
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+/// Execution counts gathered while a program runs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profile {
+    pub opcodes: HashMap<OpCode, u64>,
+    pub addresses: HashMap<usize, u64>,
+    /// Counts for each opcode with each combination of parameter modes it was run with.
+    pub modes: HashMap<(OpCode, Vec<ParameterMode>), u64>,
+    /// `JumpIfTrue` and `JumpIfFalse` outcomes, by the address of the jump.
+    pub jumps: HashMap<usize, JumpCounts>,
+}
+
+impl Profile {
+    pub fn record(&mut self, record: &TraceRecord) {
+        *self.opcodes.entry(record.opcode).or_insert(0) += 1;
+        *self.addresses.entry(record.instr_ptr).or_insert(0) += 1;
+
+        let modes = record.parameters.iter().map(|p| p.mode).collect();
+        *self.modes.entry((record.opcode, modes)).or_insert(0) += 1;
+
+        let jump_if = match record.opcode {
+            OpCode::JumpIfTrue => Some(true),
+            OpCode::JumpIfFalse => Some(false),
+            _ => None,
+        };
+        if let Some(jump_if) = jump_if {
+            let counts = self.jumps.entry(record.instr_ptr).or_default();
+            if (record.operands[0] != 0) == jump_if {
+                counts.taken += 1;
+            } else {
+                counts.not_taken += 1;
+            }
+        }
+    }
+
+    /// The number of instructions executed, which matches `Program::steps`. The `Halt` a
+    /// program stops on is traced, and counted everywhere else, but it doesn't execute, so it
+    /// is left out.
+    pub fn total(&self) -> u64 {
+        let halts = self.opcodes.get(&OpCode::Halt).copied().unwrap_or(0);
+        self.opcodes.values().sum::<u64>() - halts
+    }
+
+    /// A human-readable report with the busiest entries first. Only the `max_addresses`
+    /// busiest addresses are listed.
+    pub fn report(&self, max_addresses: usize) -> String {
+        let total = self.total();
+        let share = |count: u64| 100.0 * count as f64 / total.max(1) as f64;
+        let mut out = format!("{} instructions executed\n", total);
+
+        writeln!(out, "\n{:<8} {:>12} {:>8}", "opcode", "count", "share").unwrap();
+        for (opcode, count) in sorted(&self.opcodes) {
+            writeln!(
+                out,
+                "{:<8} {:>12} {:>7.2}%",
+                opcode.mnemonic(),
+                count,
+                share(count)
+            )
+            .unwrap();
+        }
+
+        writeln!(
+            out,
+            "\n{:<12} {:>12} {:>8}  (P = position, I = immediate, R = relative)",
+            "modes", "count", "share"
+        )
+        .unwrap();
+        for ((opcode, modes), count) in sorted(&self.modes) {
+            writeln!(
+                out,
+                "{:<12} {:>12} {:>7.2}%",
+                mode_key(opcode, &modes),
+                count,
+                share(count)
+            )
+            .unwrap();
+        }
+
+        writeln!(out, "\n{:<8} {:>12} {:>8}", "address", "count", "share").unwrap();
+        for (address, count) in sorted(&self.addresses).into_iter().take(max_addresses) {
+            writeln!(out, "{:<8} {:>12} {:>7.2}%", address, count, share(count)).unwrap();
+        }
+
+        if !self.jumps.is_empty() {
+            writeln!(
+                out,
+                "\n{:<8} {:>12} {:>12}",
+                "jump at", "taken", "not taken"
+            )
+            .unwrap();
+            let mut jumps: Vec<_> = self.jumps.iter().collect();
+            jumps.sort_by_key(|&(&address, counts)| {
+                (std::cmp::Reverse(counts.taken + counts.not_taken), address)
+            });
+            for (address, counts) in jumps {
+                writeln!(
+                    out,
+                    "{:<8} {:>12} {:>12}",
+                    address, counts.taken, counts.not_taken
+                )
+                .unwrap();
+            }
+        }
+
+        out
+    }
+
+    /// Every count as CSV with `kind,key,count` columns. Jumps get one `jump_taken` and one
+    /// `jump_not_taken` row per address.
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("kind,key,count\n");
+
+        for (opcode, count) in sorted(&self.opcodes) {
+            writeln!(out, "opcode,{},{}", opcode.mnemonic(), count).unwrap();
+        }
+        for ((opcode, modes), count) in sorted(&self.modes) {
+            writeln!(out, "modes,{},{}", mode_key(opcode, &modes), count).unwrap();
+        }
+        for (address, count) in sorted(&self.addresses) {
+            writeln!(out, "address,{},{}", address, count).unwrap();
+        }
+
+        let mut jumps: Vec<_> = self.jumps.iter().collect();
+        jumps.sort_by_key(|&(&address, _)| address);
+        for (address, counts) in jumps {
+            writeln!(out, "jump_taken,{},{}", address, counts.taken).unwrap();
+            writeln!(out, "jump_not_taken,{},{}", address, counts.not_taken).unwrap();
+        }
+
+        out
+    }
+}
+
+/// Builds a `Profile` from the instructions a `Program` executes.
+///
+/// Clones share the same profile, so one can be given to `Program::with_tracer` while another
+/// is kept to read the results.
+#[derive(Clone, Debug, Default)]
+pub struct Profiler {
+    profile: Arc<Mutex<Profile>>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler::default()
+    }
+
+    /// A copy of the counts gathered so far.
+    pub fn profile(&self) -> Profile {
+        self.profile.lock().unwrap().clone()
+    }
+}
+
+impl Tracer for Profiler {
+    fn trace(&mut self, record: &TraceRecord) {
+        self.profile.lock().unwrap().record(record);
+    }
+}
+
+/// Entries ordered by descending count. Ties are broken by key so the order is stable.
+fn sorted<K: Clone + Ord>(counts: &HashMap<K, u64>) -> Vec<(K, u64)> {
+    let mut entries: Vec<_> = counts.iter().map(|(k, &c)| (k.clone(), c)).collect();
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    entries
+}
+
+fn mode_key(opcode: OpCode, modes: &[ParameterMode]) -> String {
+    let letters: String = modes
+        .iter()
+        .map(|mode| match mode {
+            ParameterMode::Position => 'P',
+            ParameterMode::Immediate => 'I',
+            ParameterMode::Relative => 'R',
+        })
+        .collect();
+
+    format!("{} {}", opcode.mnemonic(), letters)
+        .trim_end()
+        .to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Program, Queue};
+
+    fn profile(source: &str) -> Profile {
+        let profiler = Profiler::new();
+        let mut program = Program::new(source.split(',').map(|s| s.parse().unwrap()).collect())
+            .with_output(Queue::new())
+            .with_tracer(profiler.clone());
+        program.run().unwrap();
+
+        let profile = profiler.profile();
+        assert_eq!(profile.total(), program.steps());
+        profile
+    }
+
+    #[test]
+    fn counts() {
+        // Counts down from 3, then outputs the counter and halts.
+        let profile = profile("1001,11,-1,11,1005,11,0,4,11,99,0,3");
+
+        assert_eq!(profile.total(), 7);
+        assert_eq!(profile.opcodes[&OpCode::Add], 3);
+        assert_eq!(profile.opcodes[&OpCode::JumpIfTrue], 3);
+        assert_eq!(profile.addresses[&0], 3);
+        assert_eq!(profile.addresses[&7], 1);
+        assert_eq!(
+            profile.modes[&(
+                OpCode::Add,
+                vec![
+                    ParameterMode::Position,
+                    ParameterMode::Immediate,
+                    ParameterMode::Position
+                ]
+            )],
+            3
+        );
+        assert_eq!(
+            profile.jumps[&4],
+            JumpCounts {
+                taken: 2,
+                not_taken: 1
+            }
+        );
+    }
+
+    #[test]
+    fn report_and_csv() {
+        let profile = profile("1001,11,-1,11,1005,11,0,4,11,99,0,3");
+
+        assert_eq!(
+            profile.to_csv(),
+            "kind,key,count\n\
+             opcode,ADD,3\n\
+             opcode,JT,3\n\
+             opcode,OUT,1\n\
+             opcode,HALT,1\n\
+             modes,ADD PIP,3\n\
+             modes,JT PI,3\n\
+             modes,OUT P,1\n\
+             modes,HALT,1\n\
+             address,0,3\n\
+             address,4,3\n\
+             address,7,1\n\
+             address,9,1\n\
+             jump_taken,4,2\n\
+             jump_not_taken,4,1\n"
+        );
+
+        let report = profile.report(1);
+        assert!(report.starts_with("7 instructions executed\n"));
+        assert!(report.contains("\nADD                 3   42.86%\n"));
+        assert!(report.contains("\n0                   3   42.86%\n"));
+        assert!(!report.contains("\n4                   3"));
+        assert!(report.contains("\n4                   2            1\n"));
+    }
+}
@@ -0,0 +1,1200 @@
+use crate::cache::Cache;
+use crate::history::{History, Undo};
+use crate::loops::SeenStates;
+use crate::observer::Watch;
+use crate::threaded::Compiled;
+use crate::{
+    Backend, Event, InputSource, Instruction, IntcodeError, Memory, MemoryWrite, Observer, OpCode,
+    OutputSink, Parameter, ParameterMode, Queue, Snapshot, StdinSource, StdoutSink, TraceRecord,
+    Tracer, MAX_PARAMETERS,
+};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::ops::RangeBounds;
+use std::time::{Duration, Instant};
+
+/// Addresses at or above this limit are rejected rather than growing memory to fit them.
+pub(crate) const MEMORY_LIMIT: usize = 1 << 20;
+
+/// How many steps pass between checks of the clock when a time limit is set.
+const CLOCK_CHECK_INTERVAL: u64 = 1024;
+
+/// Why a call to `Program::resume` returned.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    /// The program is waiting on an `Input` instruction. Provide more input to the input source
+    /// and resume.
+    NeedsInput,
+    /// The program produced a value. It has already been sent to the output sink.
+    Output(i64),
+    /// The program reached a `Halt` instruction. Resuming again has no effect.
+    Halted,
+}
+
+pub struct Program {
+    memory: Memory,
+    instr_ptr: usize,
+    relative_base: i64,
+    input: Box<dyn InputSource>,
+    output: Box<dyn OutputSink>,
+    /// The number of instructions executed so far.
+    steps: u64,
+    tracer: Option<Box<dyn Tracer>>,
+    /// Whether the `Halt` the program stopped on has been traced, so that resuming a halted
+    /// program doesn't trace it again.
+    halt_traced: bool,
+    /// The write made by the instruction currently executing, kept only while tracing or
+    /// recording history.
+    last_write: Option<MemoryWrite>,
+    /// Instructions that have already been decoded, by address. An entry is dropped when a
+    /// write lands anywhere inside it.
+    decoded: Cache<Instruction>,
+    cache_instructions: bool,
+    backend: Backend,
+    /// Instructions compiled by the threaded backend, by address. An entry is dropped along
+    /// with the decoded instruction at the same address.
+    compiled: Cache<Compiled>,
+    step_limit: Option<u64>,
+    time_limit: Option<Duration>,
+    /// When the first instruction was executed, kept only if there is a time limit.
+    started: Option<Instant>,
+    /// The states seen at each backward jump since the last input or output, kept only while
+    /// loop detection is on.
+    seen_states: Option<SeenStates>,
+    watches: Vec<Watch>,
+    history: Option<History>,
+}
+
+impl Program {
+    /// Creates a program from a memory image. Input is read from stdin and output is printed to
+    /// stdout until other I/O is attached.
+    pub fn new(memory: Vec<i64>) -> Self {
+        Program {
+            memory: Memory::from(memory),
+            instr_ptr: 0,
+            relative_base: 0,
+            input: Box::new(StdinSource),
+            output: Box::new(StdoutSink),
+            steps: 0,
+            tracer: None,
+            halt_traced: false,
+            last_write: None,
+            decoded: Cache::new(),
+            cache_instructions: true,
+            backend: Backend::Interpreter,
+            compiled: Cache::new(),
+            step_limit: None,
+            time_limit: None,
+            started: None,
+            seen_states: None,
+            watches: Vec::new(),
+            history: None,
+        }
+    }
+
+    pub fn with_input(mut self, input: impl InputSource + 'static) -> Self {
+        self.input = Box::new(input);
+        self
+    }
+
+    pub fn with_output(mut self, output: impl OutputSink + 'static) -> Self {
+        self.output = Box::new(output);
+        self
+    }
+
+    /// Sets whether decoded instructions are cached. Caching is on by default and only changes
+    /// how fast the program runs.
+    pub fn with_instruction_cache(mut self, enabled: bool) -> Self {
+        self.cache_instructions = enabled;
+        self.decoded.clear();
+        self.compiled.clear();
+        self
+    }
+
+    /// Selects how instructions are executed. The default is `Backend::Interpreter`.
+    ///
+    /// With `Backend::Threaded` and the instruction cache on, every address in memory that
+    /// holds a valid instruction is compiled straight away. Forks share the compiled code until
+    /// they write over it, so a search that forks one program many times only compiles it once.
+    pub fn with_backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self.compiled.clear();
+
+        if backend == Backend::Threaded && self.cache_instructions {
+            for address in 0..self.memory.len() {
+                if let Ok(instr) = Instruction::decode_with(|a| self.memory.read(a), address) {
+                    self.decoded.insert(address, instr);
+                    if let Some(compiled) = Compiled::new(&instr) {
+                        self.compiled.insert(address, compiled);
+                    }
+                }
+            }
+        }
+        self
+    }
+
+    /// Fails with `StepLimitExceeded` instead of executing more than `limit` instructions in
+    /// total.
+    pub fn with_step_limit(mut self, limit: u64) -> Self {
+        self.step_limit = Some(limit);
+        self
+    }
+
+    /// Fails with `TimeLimitExceeded` once the program has been running for longer than
+    /// `limit`, counted from its first instruction. The clock is only checked every so often,
+    /// so the limit may be overrun slightly.
+    pub fn with_time_limit(mut self, limit: Duration) -> Self {
+        self.time_limit = Some(limit);
+        self
+    }
+
+    /// Fails with `InfiniteLoop` when a backward jump would return the program to a state it
+    /// was already in without any input or output in between.
+    ///
+    /// States are memory, the jump target and the relative base. Only a hash of each one is
+    /// kept, updated as memory is written, so a backward jump doesn't copy memory. A loop is
+    /// reported on its second pass, once its state has been compared in full.
+    pub fn with_loop_detection(mut self) -> Self {
+        self.seen_states = Some(SeenStates::new(&self.memory));
+        self
+    }
+
+    /// Records an undo log of every executed instruction, so that execution can be stepped
+    /// backwards with `step_back` and `run_back_to_write`. The log grows with every step.
+    pub fn with_history(mut self) -> Self {
+        self.history = Some(History::default());
+        self
+    }
+
+    /// Reports reads, writes and taken jumps whose address is in `addresses` to `observer`.
+    /// Any number of observers can be attached.
+    pub fn with_observer(
+        mut self,
+        addresses: impl RangeBounds<usize>,
+        observer: impl Observer + 'static,
+    ) -> Self {
+        self.add_observer(addresses, observer);
+        self
+    }
+
+    /// Like `with_observer`, for a program that is already running.
+    pub fn add_observer(
+        &mut self,
+        addresses: impl RangeBounds<usize>,
+        observer: impl Observer + 'static,
+    ) {
+        self.watches.push(Watch::new(addresses, Box::new(observer)));
+    }
+
+    /// Reports every executed instruction to `tracer`.
+    pub fn with_tracer(mut self, tracer: impl Tracer + 'static) -> Self {
+        self.tracer = Some(Box::new(tracer));
+        self
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    pub fn instr_ptr(&self) -> usize {
+        self.instr_ptr
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Creates a copy of the program, in its current state, that uses different I/O.
+    ///
+    /// Memory and the instruction cache are shared between the two until either one writes to
+    /// them, so forking is cheap enough to do at every branch of a search. The
+    /// tracer and observers are not carried over, and the fork starts with an empty history.
+    pub fn fork(
+        &self,
+        input: impl InputSource + 'static,
+        output: impl OutputSink + 'static,
+    ) -> Self {
+        Program {
+            memory: self.memory.clone(),
+            instr_ptr: self.instr_ptr,
+            relative_base: self.relative_base,
+            input: Box::new(input),
+            output: Box::new(output),
+            steps: self.steps,
+            tracer: None,
+            halt_traced: self.halt_traced,
+            last_write: None,
+            decoded: self.decoded.clone(),
+            cache_instructions: self.cache_instructions,
+            backend: self.backend,
+            compiled: self.compiled.clone(),
+            step_limit: self.step_limit,
+            time_limit: self.time_limit,
+            started: None,
+            seen_states: self.seen_states.as_ref().map(SeenStates::fork),
+            watches: Vec::new(),
+            history: self.history.as_ref().map(|_| History::default()),
+        }
+    }
+
+    /// Captures the program's state, including any values still waiting in its input and output.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            memory: self.memory.to_vec(),
+            instr_ptr: self.instr_ptr,
+            relative_base: self.relative_base,
+            steps: self.steps,
+            pending_input: self.input.pending(),
+            pending_output: self.output.pending(),
+        }
+    }
+
+    /// Recreates a program from a snapshot. The snapshot's pending input and output are pushed
+    /// onto `input` and `output`, which the program then reads from and writes to.
+    pub fn from_snapshot(snapshot: &Snapshot, input: Queue, output: Queue) -> Self {
+        for &value in &snapshot.pending_input {
+            input.push(value);
+        }
+        for &value in &snapshot.pending_output {
+            output.push(value);
+        }
+
+        Program {
+            instr_ptr: snapshot.instr_ptr,
+            relative_base: snapshot.relative_base,
+            steps: snapshot.steps,
+            ..Program::new(snapshot.memory.clone())
+        }
+        .with_input(input)
+        .with_output(output)
+    }
+
+    /// Decodes the instruction at the instruction pointer without executing it.
+    pub fn current_instruction(&self) -> Result<Instruction, IntcodeError> {
+        self.decode()
+    }
+
+    /// Runs the program to completion.
+    ///
+    /// Running out of input is an error, since nothing else can provide more.
+    pub fn run(&mut self) -> Result<(), IntcodeError> {
+        loop {
+            match self.resume()? {
+                Status::Halted => return Ok(()),
+                Status::NeedsInput => {
+                    return Err(IntcodeError::InputExhausted {
+                        instr_ptr: self.instr_ptr,
+                        instruction: self.read(self.instr_ptr),
+                    })
+                }
+                Status::Output(_) => {}
+            }
+        }
+    }
+
+    /// Runs the program until it halts, produces an output, or needs input that is not yet
+    /// available. Execution can be continued by calling `resume` again.
+    ///
+    /// On error, the instruction pointer is left on the faulting instruction.
+    pub fn resume(&mut self) -> Result<Status, IntcodeError> {
+        let fast_path = self.tracer.is_none() && self.history.is_none();
+
+        loop {
+            match self.backend {
+                _ if !fast_path => {}
+                Backend::Interpreter => self.run_cached()?,
+                Backend::Threaded => self.run_compiled()?,
+            }
+            if let Some(status) = self.step()? {
+                return Ok(status);
+            }
+        }
+    }
+
+    /// Executes a single instruction. Returns the status `resume` would stop with if the
+    /// instruction halted, produced output or was blocked on input, and `None` otherwise.
+    pub fn step(&mut self) -> Result<Option<Status>, IntcodeError> {
+        let instr = self.fetch()?;
+        if instr.opcode != OpCode::Halt {
+            self.check_budget()?;
+        }
+        let instr_ptr = self.instr_ptr;
+        let operands = match self.tracer {
+            Some(_) => Some(self.resolve_operands(&instr)?),
+            None => None,
+        };
+        self.last_write = None;
+
+        let relative_base = self.relative_base;
+        let memory_len = self.memory.len();
+        let mut input = None;
+
+        let status = match instr.opcode {
+            OpCode::Halt => {
+                // Halt doesn't count as a step, since resuming runs it again every time.
+                if let (Some(tracer), false) = (self.tracer.as_mut(), self.halt_traced) {
+                    tracer.trace(&TraceRecord {
+                        step: self.steps,
+                        instr_ptr,
+                        opcode: instr.opcode,
+                        parameters: Vec::new(),
+                        operands: Vec::new(),
+                        write: None,
+                    });
+                    self.halt_traced = true;
+                }
+                return Ok(Some(Status::Halted));
+            }
+            OpCode::Input => match self.next_input() {
+                Some(value) => {
+                    self.do_input(&instr, value)?;
+                    input = Some(value);
+                    None
+                }
+                None => return Ok(Some(Status::NeedsInput)),
+            },
+            OpCode::Output => Some(Status::Output(self.do_output(&instr)?)),
+            _ => {
+                match self.backend {
+                    Backend::Interpreter => self.do_instruction(&instr)?,
+                    Backend::Threaded => self.do_compiled(&instr)?,
+                }
+                None
+            }
+        };
+
+        if let Some(history) = self.history.as_mut() {
+            history.undo.push(Undo {
+                instr_ptr,
+                relative_base,
+                memory_len,
+                write: self.last_write,
+                input,
+            });
+        }
+        if let (Some(tracer), Some(operands)) = (self.tracer.as_mut(), operands) {
+            tracer.trace(&TraceRecord {
+                step: self.steps,
+                instr_ptr,
+                opcode: instr.opcode,
+                parameters: instr.parameters().to_vec(),
+                operands,
+                write: self.last_write,
+            });
+        }
+        self.steps += 1;
+
+        Ok(status)
+    }
+
+    /// The number of executed instructions that can be undone.
+    pub fn history_len(&self) -> usize {
+        self.history.as_ref().map_or(0, |h| h.undo.len())
+    }
+
+    /// Undoes the last executed instruction, restoring memory, the instruction pointer and the
+    /// relative base to what they were before it ran. Returns false if there is nothing to
+    /// undo.
+    ///
+    /// Output that was already sent can't be taken back. Input is kept, and read again before
+    /// any new input when execution moves forwards.
+    pub fn step_back(&mut self) -> bool {
+        let undo = match self.history.as_mut().and_then(|h| h.undo.pop()) {
+            Some(undo) => undo,
+            None => return false,
+        };
+
+        if let Some(write) = undo.write {
+            self.write(write.address, write.old);
+        }
+        self.memory.truncate(undo.memory_len);
+        if let (Some(history), Some(input)) = (self.history.as_mut(), undo.input) {
+            history.replay.push(input);
+        }
+        self.instr_ptr = undo.instr_ptr;
+        self.relative_base = undo.relative_base;
+        self.steps -= 1;
+        self.halt_traced = false;
+        self.forget_states();
+
+        true
+    }
+
+    /// Steps backwards until just before the most recent instruction that wrote to `address`,
+    /// and returns that write. The instruction pointer is left on the writing instruction.
+    ///
+    /// If nothing in the history wrote to `address`, the whole history is undone and `None` is
+    /// returned.
+    pub fn run_back_to_write(&mut self, address: usize) -> Option<MemoryWrite> {
+        loop {
+            let write = self
+                .history
+                .as_ref()
+                .and_then(|h| h.undo.last())
+                .map(|undo| undo.write);
+
+            match write {
+                None => return None,
+                Some(write) => {
+                    self.step_back();
+                    if let Some(write) = write.filter(|w| w.address == address) {
+                        return Some(write);
+                    }
+                }
+            }
+        }
+    }
+
+    fn next_input(&mut self) -> Option<i64> {
+        match self.history.as_mut().and_then(|h| h.replay.pop()) {
+            Some(value) => Some(value),
+            None => self.input.next_input(),
+        }
+    }
+
+    fn check_budget(&mut self) -> Result<(), IntcodeError> {
+        if let Some(limit) = self.step_limit {
+            if self.steps >= limit {
+                return Err(IntcodeError::StepLimitExceeded {
+                    instr_ptr: self.instr_ptr,
+                    instruction: self.read(self.instr_ptr),
+                    limit,
+                });
+            }
+        }
+
+        if let Some(limit) = self.time_limit {
+            let started = *self.started.get_or_insert_with(Instant::now);
+            if self.steps.is_multiple_of(CLOCK_CHECK_INTERVAL) && started.elapsed() > limit {
+                return Err(IntcodeError::TimeLimitExceeded {
+                    instr_ptr: self.instr_ptr,
+                    instruction: self.read(self.instr_ptr),
+                    limit,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Records the state a backward jump to `target` would produce, failing if it has been
+    /// seen since the last input or output.
+    fn check_for_loop(&mut self, target: usize) -> Result<(), IntcodeError> {
+        if let Some(seen_states) = self.seen_states.as_mut() {
+            if seen_states.repeats(&self.memory, target, self.relative_base) {
+                return Err(IntcodeError::InfiniteLoop {
+                    instr_ptr: self.instr_ptr,
+                    instruction: self.read(self.instr_ptr),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn decode(&self) -> Result<Instruction, IntcodeError> {
+        match self.decoded.get(self.instr_ptr) {
+            Some(instr) => Ok(*instr),
+            _ => Instruction::decode_with(|a| self.memory.read(a), self.instr_ptr),
+        }
+    }
+
+    /// Decodes the current instruction, caching it if caching is enabled.
+    fn fetch(&mut self) -> Result<Instruction, IntcodeError> {
+        if let Some(instr) = self.decoded.get(self.instr_ptr) {
+            return Ok(*instr);
+        }
+        let instr = Instruction::decode_with(|a| self.memory.read(a), self.instr_ptr)?;
+
+        if self.cache_instructions {
+            self.decoded.insert(self.instr_ptr, instr);
+        }
+
+        Ok(instr)
+    }
+
+    /// Drops any cached instruction that `address` is part of.
+    fn invalidate(&mut self, address: usize) {
+        for start in address.saturating_sub(MAX_PARAMETERS)..=address {
+            let covers_address = matches!(
+                self.decoded.get(start),
+                Some(instr) if start + instr.opcode.instr_len() > address
+            );
+            if covers_address {
+                self.decoded.remove(start);
+                self.compiled.remove(start);
+            }
+        }
+    }
+
+    fn do_instruction(&mut self, instr: &Instruction) -> Result<(), IntcodeError> {
+        match instr.opcode {
+            OpCode::Halt | OpCode::Input | OpCode::Output => unreachable!(),
+            OpCode::Add => self.do_add(instr),
+            OpCode::Multiply => self.do_multiply(instr),
+            OpCode::JumpIfTrue => self.do_jump_if_true(instr),
+            OpCode::JumpIfFalse => self.do_jump_if_false(instr),
+            OpCode::LessThan => self.do_less_than(instr),
+            OpCode::Equals => self.do_equals(instr),
+            OpCode::AdjustRelativeBase => self.do_adjust_relative_base(instr),
+        }
+    }
+
+    /// Runs the current instruction with the threaded backend, compiling it first if it isn't
+    /// cached yet.
+    fn do_compiled(&mut self, instr: &Instruction) -> Result<(), IntcodeError> {
+        let compiled = match self.compiled.get(self.instr_ptr) {
+            Some(compiled) => *compiled,
+            None => {
+                let compiled = Compiled::new(instr).expect("step runs I/O and Halt itself");
+                if self.cache_instructions {
+                    self.compiled.insert(self.instr_ptr, compiled);
+                }
+                compiled
+            }
+        };
+
+        compiled.execute(self)
+    }
+
+    /// Runs cached instructions back to back, without going through `step`, until reaching one
+    /// that hasn't been cached yet or that `step` runs itself. Only used when there's no tracer
+    /// or history to update, since `step` is what updates them.
+    fn run_cached(&mut self) -> Result<(), IntcodeError> {
+        let limited = self.step_limit.is_some() || self.time_limit.is_some();
+
+        while let Some(&instr) = self.decoded.get(self.instr_ptr) {
+            if let OpCode::Halt | OpCode::Input | OpCode::Output = instr.opcode {
+                break;
+            }
+            if limited {
+                self.check_budget()?;
+            }
+            self.do_instruction(&instr)?;
+            self.steps += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Runs compiled instructions back to back, without going through `step`, until reaching
+    /// one that hasn't been compiled yet. Only used when there's no tracer or history to
+    /// update, since `step` is what updates them.
+    fn run_compiled(&mut self) -> Result<(), IntcodeError> {
+        while let Some(compiled) = self.compiled.get(self.instr_ptr) {
+            let compiled = *compiled;
+            self.check_budget()?;
+            compiled.execute(self)?;
+            self.steps += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Resolves each parameter to the value it reads, or to its address if it is written to.
+    fn resolve_operands(&self, instr: &Instruction) -> Result<Vec<i64>, IntcodeError> {
+        instr
+            .parameters()
+            .iter()
+            .enumerate()
+            .map(|(i, &parameter)| {
+                if instr.opcode.output_param() == Some(i) {
+                    self.get_parameter_address(parameter).map(|a| a as i64)
+                } else {
+                    self.peek_parameter_value(parameter)
+                }
+            })
+            .collect()
+    }
+
+    fn do_input(&mut self, instr: &Instruction, input: i64) -> Result<(), IntcodeError> {
+        let output_idx = self.get_parameter_address(instr.parameter(0))?;
+        self.store(output_idx, input);
+        self.forget_states();
+
+        self.instr_ptr += instr.opcode.instr_len();
+        Ok(())
+    }
+
+    fn do_output(&mut self, instr: &Instruction) -> Result<i64, IntcodeError> {
+        let value = self.get_parameter_value(instr.parameter(0))?;
+        self.output.send_output(value);
+        self.forget_states();
+
+        self.instr_ptr += instr.opcode.instr_len();
+        Ok(value)
+    }
+
+    /// Input and output can change what the program does next, so states seen before them
+    /// don't indicate a loop.
+    fn forget_states(&mut self) {
+        if let Some(seen_states) = self.seen_states.as_mut() {
+            seen_states.clear();
+        }
+    }
+
+    fn do_add(&mut self, instr: &Instruction) -> Result<(), IntcodeError> {
+        let x = self.get_parameter_value(instr.parameter(0))?;
+        let y = self.get_parameter_value(instr.parameter(1))?;
+        let output_idx = self.get_parameter_address(instr.parameter(2))?;
+
+        let sum = self.checked(x.checked_add(y))?;
+        self.store(output_idx, sum);
+
+        self.instr_ptr += instr.opcode.instr_len();
+        Ok(())
+    }
+
+    fn do_multiply(&mut self, instr: &Instruction) -> Result<(), IntcodeError> {
+        let x = self.get_parameter_value(instr.parameter(0))?;
+        let y = self.get_parameter_value(instr.parameter(1))?;
+        let output_idx = self.get_parameter_address(instr.parameter(2))?;
+
+        let product = self.checked(x.checked_mul(y))?;
+        self.store(output_idx, product);
+
+        self.instr_ptr += instr.opcode.instr_len();
+        Ok(())
+    }
+
+    fn do_jump_if_true(&mut self, instr: &Instruction) -> Result<(), IntcodeError> {
+        self.do_jump(instr, true)
+    }
+
+    fn do_jump_if_false(&mut self, instr: &Instruction) -> Result<(), IntcodeError> {
+        self.do_jump(instr, false)
+    }
+
+    fn do_less_than(&mut self, instr: &Instruction) -> Result<(), IntcodeError> {
+        self.do_comparison(instr, Ordering::Less)
+    }
+
+    fn do_equals(&mut self, instr: &Instruction) -> Result<(), IntcodeError> {
+        self.do_comparison(instr, Ordering::Equal)
+    }
+
+    fn do_adjust_relative_base(&mut self, instr: &Instruction) -> Result<(), IntcodeError> {
+        let amount = self.get_parameter_value(instr.parameter(0))?;
+        self.adjust_relative_base(amount)?;
+
+        self.advance(instr.opcode.instr_len());
+        Ok(())
+    }
+
+    pub(crate) fn adjust_relative_base(&mut self, amount: i64) -> Result<(), IntcodeError> {
+        self.relative_base = self.checked(self.relative_base.checked_add(amount))?;
+        Ok(())
+    }
+
+    /// Turns the result of a checked operation done by the current instruction into an
+    /// `Overflow` error if it overflowed.
+    pub(crate) fn checked(&self, result: Option<i64>) -> Result<i64, IntcodeError> {
+        result.ok_or_else(|| IntcodeError::Overflow {
+            instr_ptr: self.instr_ptr,
+            instruction: self.read(self.instr_ptr),
+        })
+    }
+
+    /// Moves on to the instruction after one of length `len`.
+    pub(crate) fn advance(&mut self, len: usize) {
+        self.instr_ptr += len;
+    }
+
+    /// Memory beyond the end of the loaded program is treated as zeroed.
+    ///
+    /// Observers are only told about reads made by instructions, not about calls to `read`.
+    pub fn read(&self, address: usize) -> i64 {
+        self.memory.read(address)
+    }
+
+    /// Writing past the end of memory grows it, filling the gap with zeroes.
+    ///
+    /// Observers are only told about writes made by instructions, not about calls to `write`.
+    pub fn write(&mut self, address: usize, value: i64) {
+        self.invalidate(address);
+        if let Some(seen_states) = self.seen_states.as_mut() {
+            seen_states.write(address, self.memory.read(address), value);
+        }
+        self.memory.write(address, value);
+    }
+
+    /// Reads memory on behalf of the current instruction.
+    pub(crate) fn load(&mut self, address: usize) -> i64 {
+        let value = self.read(address);
+        self.notify(Event::Read {
+            instr_ptr: self.instr_ptr,
+            address,
+            value,
+        });
+
+        value
+    }
+
+    /// Writes memory on behalf of the current instruction. Every instruction's write goes
+    /// through here.
+    pub(crate) fn store(&mut self, address: usize, value: i64) {
+        if self.tracer.is_some() || self.history.is_some() || !self.watches.is_empty() {
+            let write = MemoryWrite {
+                address,
+                old: self.read(address),
+                new: value,
+            };
+            self.last_write = Some(write);
+            self.notify(Event::Write {
+                instr_ptr: self.instr_ptr,
+                write,
+            });
+        }
+
+        self.write(address, value);
+    }
+
+    fn notify(&mut self, event: Event) {
+        for watch in &mut self.watches {
+            watch.notify(&event);
+        }
+    }
+
+    /// Checks that `address` is usable by the current instruction.
+    pub(crate) fn check_address(&self, address: i64) -> Result<usize, IntcodeError> {
+        let instr_ptr = self.instr_ptr;
+
+        match usize::try_from(address) {
+            Ok(a) if a < MEMORY_LIMIT => Ok(a),
+            Ok(_) => Err(IntcodeError::AddressOutOfBounds {
+                instr_ptr,
+                instruction: self.read(instr_ptr),
+                address,
+            }),
+            Err(_) => Err(IntcodeError::NegativeAddress {
+                instr_ptr,
+                instruction: self.read(instr_ptr),
+                address,
+            }),
+        }
+    }
+
+    fn get_parameter_address(&self, parameter: Parameter) -> Result<usize, IntcodeError> {
+        let address = match parameter.mode {
+            ParameterMode::Immediate => {
+                return Err(IntcodeError::WriteToImmediate {
+                    instr_ptr: self.instr_ptr,
+                    instruction: self.read(self.instr_ptr),
+                })
+            }
+            ParameterMode::Position => parameter.value,
+            ParameterMode::Relative => {
+                self.checked(self.relative_base.checked_add(parameter.value))?
+            }
+        };
+
+        self.check_address(address)
+    }
+
+    fn get_parameter_value(&mut self, parameter: Parameter) -> Result<i64, IntcodeError> {
+        match parameter.mode {
+            ParameterMode::Immediate => Ok(parameter.value),
+            ParameterMode::Position | ParameterMode::Relative => {
+                let address = self.get_parameter_address(parameter)?;
+                Ok(self.load(address))
+            }
+        }
+    }
+
+    /// Like `get_parameter_value`, without telling observers about the read.
+    fn peek_parameter_value(&self, parameter: Parameter) -> Result<i64, IntcodeError> {
+        match parameter.mode {
+            ParameterMode::Immediate => Ok(parameter.value),
+            ParameterMode::Position | ParameterMode::Relative => {
+                Ok(self.read(self.get_parameter_address(parameter)?))
+            }
+        }
+    }
+
+    fn do_jump(&mut self, instr: &Instruction, jump_cond: bool) -> Result<(), IntcodeError> {
+        let x = self.get_parameter_value(instr.parameter(0))?;
+        let y = self.get_parameter_value(instr.parameter(1))?;
+
+        self.jump(jump_cond == (x != 0), y, instr.opcode.instr_len())
+    }
+
+    /// Finishes a jump instruction of length `len`, moving to `target` if `taken`.
+    pub(crate) fn jump(
+        &mut self,
+        taken: bool,
+        target: i64,
+        len: usize,
+    ) -> Result<(), IntcodeError> {
+        if taken {
+            let target = self.check_address(target)?;
+            if target <= self.instr_ptr {
+                self.check_for_loop(target)?;
+            }
+            self.notify(Event::Jump {
+                instr_ptr: self.instr_ptr,
+                target,
+            });
+            self.instr_ptr = target;
+        } else {
+            self.advance(len);
+        }
+
+        Ok(())
+    }
+
+    fn do_comparison(
+        &mut self,
+        instr: &Instruction,
+        ordering: Ordering,
+    ) -> Result<(), IntcodeError> {
+        let x = self.get_parameter_value(instr.parameter(0))?;
+        let y = self.get_parameter_value(instr.parameter(1))?;
+        let output_idx = self.get_parameter_address(instr.parameter(2))?;
+
+        self.store(output_idx, if ordering == x.cmp(&y) { 1 } else { 0 });
+
+        self.instr_ptr += instr.opcode.instr_len();
+        Ok(())
+    }
+}
+
+impl TryFrom<&str> for Program {
+    type Error = IntcodeError;
+
+    fn try_from(input: &str) -> Result<Self, Self::Error> {
+        let memory = input
+            .split(',')
+            .enumerate()
+            .map(|(position, s)| {
+                s.trim()
+                    .parse()
+                    .map_err(|_| IntcodeError::MalformedProgram {
+                        position,
+                        token: s.trim().to_string(),
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Program::new(memory))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Queue;
+
+    fn load(source: &str) -> Program {
+        Program::try_from(source).unwrap()
+    }
+
+    fn run_with_inputs(program: &str, inputs: Vec<i64>) -> Vec<i64> {
+        let output = Queue::new();
+        let mut program = load(program)
+            .with_input(Queue::from(inputs))
+            .with_output(output.clone());
+        program.run().unwrap();
+
+        output.drain()
+    }
+
+    #[test]
+    fn large_numbers() {
+        let mut program = load("1102,34915192,34915192,7,4,7,99,0").with_output(Queue::new());
+        program.run().unwrap();
+
+        assert_eq!(program.memory[7], 1_219_070_632_396_864);
+        assert_eq!(
+            run_with_inputs("104,1125899906842624,99", vec![]),
+            vec![1_125_899_906_842_624]
+        );
+    }
+
+    #[test]
+    fn quine() {
+        let source = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        let expected: Vec<i64> = source.split(',').map(|s| s.parse().unwrap()).collect();
+
+        assert_eq!(run_with_inputs(source, vec![]), expected);
+    }
+
+    #[test]
+    fn boost_self_test() {
+        // Outputs the opcode of any check that fails, or a single keycode if they all pass.
+        let source = include_str!("../boost.txt").trim();
+
+        assert_eq!(
+            run_with_inputs(source, vec![1]),
+            vec![3_377_699_720_527_873]
+        );
+    }
+
+    #[test]
+    fn input_compared_to_eight() {
+        let source = "3,9,8,9,10,9,4,9,99,-1,8";
+
+        assert_eq!(run_with_inputs(source, vec![8]), vec![1]);
+        assert_eq!(run_with_inputs(source, vec![7]), vec![0]);
+    }
+
+    #[test]
+    fn resume_until_input_needed() {
+        let input = Queue::new();
+        let mut program = load("3,0,4,0,3,0,1002,0,2,0,4,0,99")
+            .with_input(input.clone())
+            .with_output(Queue::new());
+
+        assert_eq!(program.resume().unwrap(), Status::NeedsInput);
+        input.push(5);
+        assert_eq!(program.resume().unwrap(), Status::Output(5));
+        assert_eq!(program.resume().unwrap(), Status::NeedsInput);
+        assert_eq!(program.resume().unwrap(), Status::NeedsInput);
+        input.push(6);
+        assert_eq!(program.resume().unwrap(), Status::Output(12));
+        assert_eq!(program.resume().unwrap(), Status::Halted);
+        assert_eq!(program.resume().unwrap(), Status::Halted);
+    }
+
+    #[test]
+    fn step() {
+        let mut program = load("1101,2,3,0,4,0,99").with_output(Queue::new());
+
+        assert_eq!(program.step(), Ok(None));
+        assert_eq!(program.instr_ptr(), 4);
+        assert_eq!(program.read(0), 5);
+        assert_eq!(program.step(), Ok(Some(Status::Output(5))));
+        assert_eq!(program.step(), Ok(Some(Status::Halted)));
+        assert_eq!(program.instr_ptr(), 6);
+    }
+
+    #[test]
+    fn fork() {
+        let mut program = load("3,9,1001,9,1,9,4,9,99,0").with_input(Queue::from(vec![5]));
+        program.step().unwrap();
+
+        let output = Queue::new();
+        let mut forked = program.fork(Queue::new(), output.clone());
+        forked.write(9, 10);
+        forked.run().unwrap();
+
+        assert_eq!(output.drain(), vec![11]);
+        assert_eq!(program.read(9), 5);
+        assert_eq!(program.instr_ptr(), 2);
+    }
+
+    #[test]
+    fn self_modifying_code_invalidates_cache() {
+        // Increments the first operand of the instruction at address 0 before running it again.
+        let source = "1101,1,1,30,1001,1,1,1,1001,31,1,31,1008,31,2,32,1006,32,0,4,30,99";
+
+        for &cached in &[true, false] {
+            let output = Queue::new();
+            let mut program = load(source)
+                .with_instruction_cache(cached)
+                .with_output(output.clone());
+            program.run().unwrap();
+
+            assert_eq!(output.drain(), vec![3]);
+        }
+    }
+
+    #[test]
+    fn step_limit() {
+        // Counts down from 3, then halts.
+        let source = "1001,9,-1,9,1005,9,0,99,0,3";
+
+        assert_eq!(load(source).with_step_limit(6).run(), Ok(()));
+        assert_eq!(
+            load(source).with_step_limit(5).run(),
+            Err(IntcodeError::StepLimitExceeded {
+                instr_ptr: 4,
+                instruction: 1005,
+                limit: 5
+            })
+        );
+    }
+
+    #[test]
+    fn time_limit() {
+        let limit = Duration::from_millis(20);
+
+        assert_eq!(
+            load("1105,1,0").with_time_limit(limit).run(),
+            Err(IntcodeError::TimeLimitExceeded {
+                instr_ptr: 0,
+                instruction: 1105,
+                limit
+            })
+        );
+    }
+
+    #[test]
+    fn loop_detection() {
+        assert_eq!(
+            load("1105,1,0").with_loop_detection().run(),
+            Err(IntcodeError::InfiniteLoop {
+                instr_ptr: 0,
+                instruction: 1105
+            })
+        );
+
+        // Counting down changes memory on every pass, so it isn't a loop.
+        assert_eq!(
+            load("1001,9,-1,9,1005,9,0,99,0,3")
+                .with_loop_detection()
+                .run(),
+            Ok(())
+        );
+
+        // Flips the sign of address 9 forever, so memory changes on every pass but repeats.
+        assert_eq!(
+            load("1002,9,-1,9,1105,1,0,99,0,1")
+                .with_loop_detection()
+                .run(),
+            Err(IntcodeError::InfiniteLoop {
+                instr_ptr: 4,
+                instruction: 1105
+            })
+        );
+
+        // Echoes input forever. Reading input resets detection, so it only ever runs out.
+        assert_eq!(
+            load("3,7,4,7,1105,1,0,0")
+                .with_loop_detection()
+                .with_input(Queue::from(vec![1, 2, 3]))
+                .with_output(Queue::new())
+                .run(),
+            Err(IntcodeError::InputExhausted {
+                instr_ptr: 0,
+                instruction: 3
+            })
+        );
+    }
+
+    #[test]
+    fn closure_io() {
+        let outputs = Queue::new();
+        let collected = outputs.clone();
+        let mut program = load("3,0,102,2,0,0,4,0,99")
+            .with_input(|| Some(21))
+            .with_output(move |value| collected.push(value));
+        program.run().unwrap();
+
+        assert_eq!(outputs.drain(), vec![42]);
+    }
+
+    #[test]
+    fn relative_base_write_grows_memory() {
+        // Moves the relative base to 10, then writes 5 + 6 to address 10 + 2.
+        let mut program = load("109,10,21101,5,6,2,99");
+        program.run().unwrap();
+
+        assert_eq!(program.relative_base, 10);
+        assert_eq!(program.memory.len(), 13);
+        assert_eq!(program.memory[12], 11);
+    }
+
+    #[test]
+    fn relative_base_read() {
+        // Reads address 1 via a negative relative offset and copies it to address 20.
+        let mut program = load("109,5,22101,0,-4,15,99");
+        program.run().unwrap();
+
+        assert_eq!(program.memory[20], 5);
+    }
+
+    #[test]
+    fn read_past_end_is_zero() {
+        let mut program = load("1001,100,7,9,99");
+        program.run().unwrap();
+
+        assert_eq!(program.memory[9], 7);
+    }
+
+    #[test]
+    fn malformed_program() {
+        assert_eq!(
+            Program::try_from("1,2,x,4").err(),
+            Some(IntcodeError::MalformedProgram {
+                position: 2,
+                token: "x".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn runtime_errors() {
+        assert_eq!(
+            load("1,0,0,0,42").run(),
+            Err(IntcodeError::UnknownOpCode {
+                instr_ptr: 4,
+                instruction: 42
+            })
+        );
+        assert_eq!(
+            load("1101,1,1,5,304,0,99").run(),
+            Err(IntcodeError::UnknownParameterMode {
+                instr_ptr: 4,
+                instruction: 304,
+                mode: 3
+            })
+        );
+        assert_eq!(
+            load("1101,1,1,-3,99").run(),
+            Err(IntcodeError::NegativeAddress {
+                instr_ptr: 0,
+                instruction: 1101,
+                address: -3
+            })
+        );
+        assert_eq!(
+            load("1105,1,9999999,99").run(),
+            Err(IntcodeError::AddressOutOfBounds {
+                instr_ptr: 0,
+                instruction: 1105,
+                address: 9_999_999
+            })
+        );
+        assert_eq!(
+            load("11101,1,1,0,99").run(),
+            Err(IntcodeError::WriteToImmediate {
+                instr_ptr: 0,
+                instruction: 11101
+            })
+        );
+        assert_eq!(
+            load("3,0,99").with_input(Queue::new()).run(),
+            Err(IntcodeError::InputExhausted {
+                instr_ptr: 0,
+                instruction: 3
+            })
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        let max = i64::MAX.to_string();
+        let cases = [
+            (format!("1101,{},1,0,99", max), 0, 1101),
+            (format!("1102,{},2,0,99", max), 0, 1102),
+            (format!("109,{},109,1,99", max), 2, 109),
+            (format!("109,{},1201,1,0,0,99", max), 2, 1201),
+        ];
+
+        for backend in [Backend::Interpreter, Backend::Threaded] {
+            for (source, instr_ptr, instruction) in &cases {
+                assert_eq!(
+                    load(source).with_backend(backend).run(),
+                    Err(IntcodeError::Overflow {
+                        instr_ptr: *instr_ptr,
+                        instruction: *instruction
+                    })
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use crate::{Program, Queue};
+use std::convert::TryFrom;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// How many combinations a thread takes at a time.
+const CHUNK_SIZE: usize = 16;
+
+/// Searches for values to write into a program's memory so that it halts with a given value at
+/// a given address, as in day 2's noun and verb.
+///
+/// Every combination of values is tried on a fork of the program, split between threads.
+/// Combinations for which the program fails, including by hitting its step limit or looping
+/// forever, aren't solutions.
+pub struct GoalSearch {
+    program: Program,
+    positions: Vec<(usize, RangeInclusive<i64>)>,
+    target_address: usize,
+    target_value: i64,
+    threads: usize,
+    limit: Option<usize>,
+}
+
+impl GoalSearch {
+    /// Any limits, loop detection or backend should be set on `program` beforehand. Forks
+    /// share its compiled code until they write over it, so with `Backend::Threaded` the
+    /// program is only compiled once.
+    pub fn new(program: Program, target_address: usize, target_value: i64) -> Self {
+        GoalSearch {
+            program,
+            positions: Vec::new(),
+            target_address,
+            target_value,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            limit: None,
+        }
+    }
+
+    /// Tries every value in `values` at `address`.
+    pub fn with_position(mut self, address: usize, values: RangeInclusive<i64>) -> Self {
+        self.positions.push((address, values));
+        self
+    }
+
+    /// Runs the search on this many threads instead of one per CPU core.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Stops the search once the first `limit` solutions are known.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Returns the values of every solution, one per position in the order they were added.
+    ///
+    /// Solutions are ordered by the value at the first position, then the second, and so on.
+    /// With a limit, they are the first ones in that order.
+    pub fn run(&self) -> Vec<Vec<i64>> {
+        let total = self
+            .positions
+            .iter()
+            .try_fold(1usize, |total, (_, values)| {
+                total.checked_mul(range_len(values)?)
+            })
+            .expect("Too many combinations to search");
+
+        let next = AtomicUsize::new(0);
+        // Combinations past this one can't be among the first `limit` solutions.
+        let cutoff = AtomicUsize::new(usize::MAX);
+        let found = Mutex::new(Vec::new());
+        let (positions, target, limit) = (
+            &self.positions[..],
+            (self.target_address, self.target_value),
+            self.limit,
+        );
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                let base = self.program.fork(Queue::new(), Queue::new());
+                let (next, cutoff, found) = (&next, &cutoff, &found);
+
+                scope.spawn(move || loop {
+                    let start = next.fetch_add(CHUNK_SIZE, Ordering::Relaxed);
+                    if start >= total.min(cutoff.load(Ordering::Relaxed)) {
+                        break;
+                    }
+
+                    for index in start..(start + CHUNK_SIZE).min(total) {
+                        if index > cutoff.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        let values = combination(positions, index);
+                        if is_solution(&base, positions, target, &values) {
+                            record(found, cutoff, limit, index, values);
+                        }
+                    }
+                });
+            }
+        });
+
+        let mut found = found.into_inner().unwrap();
+        found.sort_unstable_by_key(|&(index, _)| index);
+        found.truncate(self.limit.unwrap_or(usize::MAX));
+        found.into_iter().map(|(_, values)| values).collect()
+    }
+}
+
+/// The number of values in `range`, or `None` if that doesn't fit in a `usize`. Worked out in
+/// `i128`, since a range of `i64`s can hold more values than an `i64` can count.
+fn range_len(range: &RangeInclusive<i64>) -> Option<usize> {
+    let len = i128::from(*range.end()) - i128::from(*range.start()) + 1;
+    usize::try_from(len.max(0)).ok()
+}
+
+/// The values of the `index`th combination, where the last position varies fastest. Every
+/// range's length must fit in a `usize`, which `run` checks.
+fn combination(positions: &[(usize, RangeInclusive<i64>)], mut index: usize) -> Vec<i64> {
+    let mut values = vec![0; positions.len()];
+    for (value, (_, range)) in values.iter_mut().zip(positions).rev() {
+        let len = range_len(range).unwrap();
+        // The offset can be past `i64::MAX` even though the value it leads to isn't.
+        *value = (i128::from(*range.start()) + (index % len) as i128) as i64;
+        index /= len;
+    }
+    values
+}
+
+/// Whether the program halts with `target.1` at address `target.0` once `values` are written
+/// to their positions.
+fn is_solution(
+    base: &Program,
+    positions: &[(usize, RangeInclusive<i64>)],
+    target: (usize, i64),
+    values: &[i64],
+) -> bool {
+    let mut program = base.fork(Queue::new(), Queue::new());
+    for (&(address, _), &value) in positions.iter().zip(values) {
+        program.write(address, value);
+    }
+
+    program.run().is_ok() && program.read(target.0) == target.1
+}
+
+/// Adds a solution, and once there are at least `limit` of them, lowers the cutoff to the
+/// `limit`th one.
+fn record(
+    found: &Mutex<Vec<(usize, Vec<i64>)>>,
+    cutoff: &AtomicUsize,
+    limit: Option<usize>,
+    index: usize,
+    values: Vec<i64>,
+) {
+    let mut found = found.lock().unwrap();
+    found.push((index, values));
+
+    if let Some(limit) = limit.filter(|&limit| found.len() >= limit) {
+        let mut indices: Vec<usize> = found.iter().map(|&(index, _)| index).collect();
+        indices.sort_unstable();
+        cutoff.fetch_min(indices[limit.max(1) - 1], Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Backend;
+
+    /// Halts with the sum of its second and third words at address 0, but fails if either is
+    /// negative since the first instruction reads from those addresses.
+    fn search() -> GoalSearch {
+        let program =
+            Program::new(vec![1, 0, 0, 3, 1, 1, 2, 0, 99]).with_backend(Backend::Threaded);
+        GoalSearch::new(program, 0, 3)
+            .with_position(1, -2..=5)
+            .with_position(2, -2..=5)
+    }
+
+    #[test]
+    fn every_solution() {
+        let expected = vec![vec![0, 3], vec![1, 2], vec![2, 1], vec![3, 0]];
+        assert_eq!(search().with_threads(1).run(), expected);
+        assert_eq!(search().with_threads(3).run(), expected);
+    }
+
+    #[test]
+    fn stops_at_limit() {
+        for &threads in &[1, 4] {
+            let solutions = search().with_threads(threads).with_limit(2).run();
+            assert_eq!(solutions, vec![vec![0, 3], vec![1, 2]]);
+        }
+    }
+
+    #[test]
+    fn ranges_wider_than_i64() {
+        let program = Program::new(vec![1, 0, 0, 3, 1, 1, 2, 0, 99]);
+        let solutions = GoalSearch::new(program, 0, 3)
+            .with_position(1, -1..=i64::MAX)
+            .with_position(2, 3..=3)
+            .with_threads(1)
+            .with_limit(1)
+            .run();
+        assert_eq!(solutions, vec![vec![0, 3]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many combinations to search")]
+    fn too_many_combinations() {
+        search().with_position(3, i64::MIN..=i64::MAX).run();
+    }
+}
@@ -0,0 +1,296 @@
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const MAGIC: &[u8; 6] = b"ICSNAP";
+const VERSION: u16 = 1;
+
+/// The complete state of a paused `Program`, taken with `Program::snapshot` and resumed with
+/// `Program::from_snapshot`.
+///
+/// Pending input and output are only captured when the program's I/O is queue-backed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub memory: Vec<i64>,
+    pub instr_ptr: usize,
+    pub relative_base: i64,
+    pub steps: u64,
+    pub pending_input: Vec<i64>,
+    pub pending_output: Vec<i64>,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    /// The data doesn't start with the snapshot magic bytes.
+    NotASnapshot,
+    UnsupportedVersion(u16),
+    ChecksumMismatch {
+        expected: u32,
+        actual: u32,
+    },
+    /// The data ended before the snapshot did.
+    Truncated,
+    /// There is more data after the last section.
+    TrailingData,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "{}", e),
+            SnapshotError::NotASnapshot => write!(f, "not an Intcode snapshot"),
+            SnapshotError::UnsupportedVersion(v) => {
+                write!(f, "unsupported snapshot version {}", v)
+            }
+            SnapshotError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "snapshot checksum mismatch (expected {:08x}, found {:08x})",
+                expected, actual
+            ),
+            SnapshotError::Truncated => write!(f, "snapshot is truncated"),
+            SnapshotError::TrailingData => write!(f, "snapshot has data after its last section"),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+impl Snapshot {
+    /// Serializes the snapshot.
+    ///
+    /// The format is the magic bytes `ICSNAP`, a little-endian `u16` version, the instruction
+    /// pointer, relative base and step count as 64-bit integers, then memory, pending input and
+    /// pending output, each as a `u64` length followed by that many `i64`s. A CRC-32 of
+    /// everything before it ends the file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.instr_ptr as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.relative_base.to_le_bytes());
+        bytes.extend_from_slice(&self.steps.to_le_bytes());
+
+        for values in &[&self.memory, &self.pending_input, &self.pending_output] {
+            bytes.extend_from_slice(&(values.len() as u64).to_le_bytes());
+            for value in values.iter() {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        let checksum = crc32(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(SnapshotError::NotASnapshot);
+        }
+
+        let mut reader = Reader {
+            bytes,
+            pos: MAGIC.len(),
+        };
+        let version = u16::from_le_bytes(reader.take()?);
+        if version != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        if bytes.len() < reader.pos + 4 {
+            return Err(SnapshotError::Truncated);
+        }
+        let (body, checksum) = bytes.split_at(bytes.len() - 4);
+        let expected = u32::from_le_bytes(checksum.try_into().unwrap());
+        let actual = crc32(body);
+        if expected != actual {
+            return Err(SnapshotError::ChecksumMismatch { expected, actual });
+        }
+
+        let mut reader = Reader {
+            bytes: body,
+            pos: reader.pos,
+        };
+        let instr_ptr = u64::from_le_bytes(reader.take()?) as usize;
+        let relative_base = i64::from_le_bytes(reader.take()?);
+        let steps = u64::from_le_bytes(reader.take()?);
+        let memory = reader.values()?;
+        let pending_input = reader.values()?;
+        let pending_output = reader.values()?;
+        if reader.pos != body.len() {
+            return Err(SnapshotError::TrailingData);
+        }
+
+        Ok(Snapshot {
+            memory,
+            instr_ptr,
+            relative_base,
+            steps,
+            pending_input,
+            pending_output,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        Snapshot::from_bytes(&fs::read(path)?)
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], SnapshotError> {
+        let end = self.pos + N;
+        let chunk = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or(SnapshotError::Truncated)?;
+        self.pos = end;
+        Ok(chunk.try_into().unwrap())
+    }
+
+    fn values(&mut self) -> Result<Vec<i64>, SnapshotError> {
+        let len = u64::from_le_bytes(self.take()?) as usize;
+        if len > (self.bytes.len() - self.pos) / 8 {
+            return Err(SnapshotError::Truncated);
+        }
+
+        (0..len)
+            .map(|_| Ok(i64::from_le_bytes(self.take()?)))
+            .collect()
+    }
+}
+
+/// CRC-32 (IEEE 802.3), computed bitwise.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffff_u32;
+
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Program, Queue, Status};
+    use std::{env, process};
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn save_and_resume() {
+        // Adds up inputs until it reads a zero, outputting the running total each time.
+        let source = "3,20,1006,20,19,1,20,21,21,4,21,1105,1,0,0,0,0,0,0,99,0,0";
+        let input = Queue::from(vec![5, 7]);
+        let output = Queue::new();
+        let mut program = Program::new(source.split(',').map(|s| s.parse().unwrap()).collect())
+            .with_input(input.clone())
+            .with_output(output.clone());
+
+        assert_eq!(program.resume().unwrap(), Status::Output(5));
+        let snapshot = program.snapshot();
+        assert_eq!(snapshot.pending_input, vec![7]);
+        assert_eq!(snapshot.pending_output, vec![5]);
+
+        let restored = Snapshot::from_bytes(&snapshot.to_bytes()).unwrap();
+        assert_eq!(restored, snapshot);
+
+        // Both copies carry on independently from the same point.
+        for mut program in [
+            program,
+            Program::from_snapshot(&restored, Queue::new(), Queue::new()),
+        ] {
+            assert_eq!(program.resume().unwrap(), Status::Output(12));
+            assert_eq!(program.resume().unwrap(), Status::NeedsInput);
+        }
+    }
+
+    #[test]
+    fn corrupt_snapshots() {
+        let snapshot = Program::new(vec![99]).snapshot();
+        let mut bytes = snapshot.to_bytes();
+
+        assert!(matches!(
+            Snapshot::from_bytes(b"hello"),
+            Err(SnapshotError::NotASnapshot)
+        ));
+        assert!(matches!(
+            Snapshot::from_bytes(&bytes[..bytes.len() - 6]),
+            Err(SnapshotError::ChecksumMismatch { .. })
+        ));
+
+        bytes[MAGIC.len()] = 9;
+        assert!(matches!(
+            Snapshot::from_bytes(&bytes),
+            Err(SnapshotError::UnsupportedVersion(9))
+        ));
+
+        let mut bytes = snapshot.to_bytes();
+        bytes[20] ^= 1;
+        assert!(matches!(
+            Snapshot::from_bytes(&bytes),
+            Err(SnapshotError::ChecksumMismatch { .. })
+        ));
+
+        // Extra bytes before a checksum that covers them.
+        let mut bytes = snapshot.to_bytes();
+        bytes.truncate(bytes.len() - 4);
+        bytes.extend_from_slice(&[0; 8]);
+        let checksum = crc32(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        assert!(matches!(
+            Snapshot::from_bytes(&bytes),
+            Err(SnapshotError::TrailingData)
+        ));
+    }
+
+    #[test]
+    fn files() {
+        let path = env::temp_dir().join(format!("intcode_snapshot_{}", process::id()));
+        let snapshot = Program::new(vec![1101, 2, 3, 5, 99, 0]).snapshot();
+
+        snapshot.save(&path).unwrap();
+        assert_eq!(Snapshot::load(&path).unwrap(), snapshot);
+
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[MAGIC.len() + 2] ^= 0x80;
+        fs::write(&path, &bytes).unwrap();
+        assert!(matches!(
+            Snapshot::load(&path),
+            Err(SnapshotError::ChecksumMismatch { .. })
+        ));
+
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(Snapshot::load(&path), Err(SnapshotError::Io(_))));
+    }
+}
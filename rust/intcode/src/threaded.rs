@@ -0,0 +1,251 @@
+use crate::{Instruction, IntcodeError, OpCode, Parameter, ParameterMode, Program};
+
+/// How a `Program` executes instructions. Both backends behave identically, including for
+/// limits, loop detection, tracing, observers, history and self-modifying code.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Backend {
+    /// Dispatches on the opcode and parameter modes of each instruction every time it runs.
+    #[default]
+    Interpreter,
+    /// Compiles each instruction the first time it runs into a handler for its opcode and an
+    /// accessor for each parameter's mode, then calls those directly every time after that.
+    /// Compiled instructions are kept in the instruction cache and dropped along with it when
+    /// the program writes over them.
+    Threaded,
+}
+
+/// Reads an operand, given its parameter's value.
+type Read = fn(&mut Program, i64) -> Result<i64, IntcodeError>;
+/// Resolves the address an instruction writes to, given its parameter's value.
+type Locate = fn(&Program, i64) -> Result<usize, IntcodeError>;
+type Handler = fn(&mut Program, &Compiled) -> Result<(), IntcodeError>;
+
+/// An instruction compiled for the threaded backend.
+#[derive(Clone, Copy)]
+pub(crate) struct Compiled {
+    handler: Handler,
+    reads: [(Read, i64); 2],
+    target: (Locate, i64),
+    len: usize,
+}
+
+impl Compiled {
+    /// `None` for `Input`, `Output` and `Halt`, which `Program::step` runs itself.
+    pub(crate) fn new(instr: &Instruction) -> Option<Self> {
+        let handler: Handler = match instr.opcode {
+            OpCode::Add => add,
+            OpCode::Multiply => multiply,
+            OpCode::LessThan => less_than,
+            OpCode::Equals => equals,
+            OpCode::JumpIfTrue => jump_if_true,
+            OpCode::JumpIfFalse => jump_if_false,
+            OpCode::AdjustRelativeBase => adjust_relative_base,
+            OpCode::Input | OpCode::Output | OpCode::Halt => return None,
+        };
+
+        let parameters = instr.parameters();
+        let read = |i: usize| match parameters.get(i) {
+            Some(&parameter) => (reader(parameter), parameter.value),
+            None => (read_immediate as Read, 0),
+        };
+        let target = match instr.opcode.output_param() {
+            Some(i) => (locator(parameters[i]), parameters[i].value),
+            None => (locate_immediate as Locate, 0),
+        };
+
+        Some(Compiled {
+            handler,
+            reads: [read(0), read(1)],
+            target,
+            len: instr.opcode.instr_len(),
+        })
+    }
+
+    pub(crate) fn execute(&self, program: &mut Program) -> Result<(), IntcodeError> {
+        (self.handler)(program, self)
+    }
+
+    fn read(&self, program: &mut Program, i: usize) -> Result<i64, IntcodeError> {
+        let (read, value) = self.reads[i];
+        read(program, value)
+    }
+
+    fn write(&self, program: &mut Program, value: i64) -> Result<(), IntcodeError> {
+        let (locate, parameter) = self.target;
+        let address = locate(program, parameter)?;
+        program.store(address, value);
+        program.advance(self.len);
+        Ok(())
+    }
+}
+
+fn reader(parameter: Parameter) -> Read {
+    match parameter.mode {
+        ParameterMode::Position => read_position,
+        ParameterMode::Immediate => read_immediate,
+        ParameterMode::Relative => read_relative,
+    }
+}
+
+fn locator(parameter: Parameter) -> Locate {
+    match parameter.mode {
+        ParameterMode::Position => locate_position,
+        ParameterMode::Immediate => locate_immediate,
+        ParameterMode::Relative => locate_relative,
+    }
+}
+
+fn read_position(program: &mut Program, value: i64) -> Result<i64, IntcodeError> {
+    let address = locate_position(program, value)?;
+    Ok(program.load(address))
+}
+
+fn read_immediate(_: &mut Program, value: i64) -> Result<i64, IntcodeError> {
+    Ok(value)
+}
+
+fn read_relative(program: &mut Program, value: i64) -> Result<i64, IntcodeError> {
+    let address = locate_relative(program, value)?;
+    Ok(program.load(address))
+}
+
+fn locate_position(program: &Program, value: i64) -> Result<usize, IntcodeError> {
+    program.check_address(value)
+}
+
+fn locate_immediate(program: &Program, _: i64) -> Result<usize, IntcodeError> {
+    Err(IntcodeError::WriteToImmediate {
+        instr_ptr: program.instr_ptr(),
+        instruction: program.read(program.instr_ptr()),
+    })
+}
+
+fn locate_relative(program: &Program, value: i64) -> Result<usize, IntcodeError> {
+    let address = program.checked(program.relative_base().checked_add(value))?;
+    program.check_address(address)
+}
+
+fn add(program: &mut Program, instr: &Compiled) -> Result<(), IntcodeError> {
+    let (x, y) = (instr.read(program, 0)?, instr.read(program, 1)?);
+    let value = program.checked(x.checked_add(y))?;
+    instr.write(program, value)
+}
+
+fn multiply(program: &mut Program, instr: &Compiled) -> Result<(), IntcodeError> {
+    let (x, y) = (instr.read(program, 0)?, instr.read(program, 1)?);
+    let value = program.checked(x.checked_mul(y))?;
+    instr.write(program, value)
+}
+
+fn less_than(program: &mut Program, instr: &Compiled) -> Result<(), IntcodeError> {
+    let value = instr.read(program, 0)? < instr.read(program, 1)?;
+    instr.write(program, value as i64)
+}
+
+fn equals(program: &mut Program, instr: &Compiled) -> Result<(), IntcodeError> {
+    let value = instr.read(program, 0)? == instr.read(program, 1)?;
+    instr.write(program, value as i64)
+}
+
+fn jump_if_true(program: &mut Program, instr: &Compiled) -> Result<(), IntcodeError> {
+    let condition = instr.read(program, 0)?;
+    let target = instr.read(program, 1)?;
+    program.jump(condition != 0, target, instr.len)
+}
+
+fn jump_if_false(program: &mut Program, instr: &Compiled) -> Result<(), IntcodeError> {
+    let condition = instr.read(program, 0)?;
+    let target = instr.read(program, 1)?;
+    program.jump(condition == 0, target, instr.len)
+}
+
+fn adjust_relative_base(program: &mut Program, instr: &Compiled) -> Result<(), IntcodeError> {
+    let amount = instr.read(program, 0)?;
+    program.adjust_relative_base(amount)?;
+    program.advance(instr.len);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{assemble, Queue};
+
+    fn run(memory: &[i64], backend: Backend, inputs: Vec<i64>) -> (Vec<i64>, Vec<i64>) {
+        let output = Queue::new();
+        let mut program = Program::new(memory.to_vec())
+            .with_backend(backend)
+            .with_input(Queue::from(inputs))
+            .with_output(output.clone());
+        program.run().unwrap();
+
+        (program.memory().to_vec(), output.drain())
+    }
+
+    #[test]
+    fn backends_agree() {
+        // Compares its input with 8 using every parameter mode.
+        let memory = assemble(
+            "       ARB #100
+                    IN -> [rb+1]
+                    EQ [rb+1], #8 -> [rb+2]
+                    LT #8, [rb+1] -> [x]
+                    MUL [x], #2 -> [x]
+                    ADD [x], [rb+2] -> [rb+3]
+                    JF [rb+3], #zero
+                    OUT [rb+3]
+                    HALT
+             zero:  OUT #-1
+                    HALT
+             x:     data 0",
+        )
+        .unwrap();
+
+        for &input in &[7, 8, 9] {
+            assert_eq!(
+                run(&memory, Backend::Threaded, vec![input]),
+                run(&memory, Backend::Interpreter, vec![input])
+            );
+        }
+    }
+
+    #[test]
+    fn self_modifying_code() {
+        // Runs the instruction at `patch` as an ADD, then turns it into a MUL and runs it again.
+        let memory = assemble(
+            "patch: ADD [v], [v] -> [v]
+                    JF [n], #done
+                    ADD #0, #0 -> [n]
+                    ADD [patch], #1 -> [patch]
+                    JT #1, #patch
+             done:  OUT [v]
+                    HALT
+             v:     data 3
+             n:     data 1",
+        )
+        .unwrap();
+
+        let threaded = run(&memory, Backend::Threaded, vec![]);
+        assert_eq!(threaded.1, vec![36]);
+        assert_eq!(threaded, run(&memory, Backend::Interpreter, vec![]));
+    }
+
+    #[test]
+    fn forks_share_compiled_code() {
+        let program = Program::new(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50])
+            .with_backend(Backend::Threaded);
+
+        // Patching the operands of the first instruction recompiles it in that fork alone.
+        let mut results = Vec::new();
+        for &(noun, verb) in &[(9, 10), (10, 9), (11, 11), (9, 10)] {
+            let mut fork = program.fork(Queue::new(), Queue::new());
+            fork.write(1, noun);
+            fork.write(2, verb);
+            fork.run().unwrap();
+            results.push(fork.read(0));
+        }
+
+        assert_eq!(results, vec![3500, 3500, 5000, 3500]);
+        assert_eq!(program.read(0), 1);
+    }
+}
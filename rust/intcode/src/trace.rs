@@ -0,0 +1,205 @@
+use crate::{OpCode, Parameter, ParameterMode};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+/// A memory write made by a single instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemoryWrite {
+    pub address: usize,
+    pub old: i64,
+    pub new: i64,
+}
+
+/// Everything one executed instruction did.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceRecord {
+    /// How many instructions were executed before this one.
+    pub step: u64,
+    pub instr_ptr: usize,
+    pub opcode: OpCode,
+    pub parameters: Vec<Parameter>,
+    /// What each parameter resolved to before the instruction ran: the value read for input
+    /// parameters, and the target address for the parameter being written.
+    pub operands: Vec<i64>,
+    pub write: Option<MemoryWrite>,
+}
+
+impl TraceRecord {
+    /// Formats the record as a single line of JSON.
+    pub fn to_json(&self) -> String {
+        let mut json = format!(
+            r#"{{"step":{},"instr_ptr":{},"opcode":"{}","parameters":["#,
+            self.step,
+            self.instr_ptr,
+            self.opcode.mnemonic()
+        );
+
+        for (i, parameter) in self.parameters.iter().enumerate() {
+            let mode = match parameter.mode {
+                ParameterMode::Position => "position",
+                ParameterMode::Immediate => "immediate",
+                ParameterMode::Relative => "relative",
+            };
+            let separator = if i == 0 { "" } else { "," };
+            write!(
+                json,
+                r#"{}{{"value":{},"mode":"{}"}}"#,
+                separator, parameter.value, mode
+            )
+            .unwrap();
+        }
+
+        let operands: Vec<String> = self.operands.iter().map(|o| o.to_string()).collect();
+        write!(json, r#"],"operands":[{}],"write":"#, operands.join(",")).unwrap();
+
+        match self.write {
+            Some(w) => write!(
+                json,
+                r#"{{"address":{},"old":{},"new":{}}}}}"#,
+                w.address, w.old, w.new
+            )
+            .unwrap(),
+            None => json.push_str("null}"),
+        }
+
+        json
+    }
+}
+
+/// Receives a record for every instruction a `Program` executes.
+pub trait Tracer: Send {
+    fn trace(&mut self, record: &TraceRecord);
+}
+
+impl<F> Tracer for F
+where
+    F: FnMut(&TraceRecord) + Send,
+{
+    fn trace(&mut self, record: &TraceRecord) {
+        self(record)
+    }
+}
+
+/// Writes each record to `W` as a line of JSON (the JSON Lines format).
+///
+/// Writing stops at the first error, e.g. when the trace is piped into `head`, and the program
+/// carries on without it.
+pub struct JsonLinesTracer<W> {
+    writer: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write + Send> JsonLinesTracer<W> {
+    pub fn new(writer: W) -> Self {
+        JsonLinesTracer {
+            writer,
+            error: None,
+        }
+    }
+
+    /// The error that stopped the trace, if any.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + Send> Tracer for JsonLinesTracer<W> {
+    fn trace(&mut self, record: &TraceRecord) {
+        if self.error.is_none() {
+            if let Err(e) = writeln!(self.writer, "{}", record.to_json()) {
+                self.error = Some(e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Program, Queue};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn records_each_instruction() {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let collected = records.clone();
+        let mut program = Program::new(vec![1001, 5, 7, 5, 99, 3])
+            .with_output(Queue::new())
+            .with_tracer(move |r: &TraceRecord| collected.lock().unwrap().push(r.clone()));
+        program.run().unwrap();
+        // Resuming a halted program doesn't trace the HALT again.
+        program.resume().unwrap();
+
+        assert_eq!(
+            *records.lock().unwrap(),
+            vec![
+                TraceRecord {
+                    step: 0,
+                    instr_ptr: 0,
+                    opcode: OpCode::Add,
+                    parameters: vec![
+                        Parameter::new(5, ParameterMode::Position),
+                        Parameter::new(7, ParameterMode::Immediate),
+                        Parameter::new(5, ParameterMode::Position),
+                    ],
+                    operands: vec![3, 7, 5],
+                    write: Some(MemoryWrite {
+                        address: 5,
+                        old: 3,
+                        new: 10
+                    }),
+                },
+                TraceRecord {
+                    step: 1,
+                    instr_ptr: 4,
+                    opcode: OpCode::Halt,
+                    parameters: vec![],
+                    operands: vec![],
+                    write: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn json_lines() {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let collected = records.clone();
+        let mut program = Program::new(vec![109, 3, 204, -1, 99])
+            .with_output(Queue::new())
+            .with_tracer(move |r: &TraceRecord| collected.lock().unwrap().push(r.clone()));
+        program.run().unwrap();
+
+        let mut tracer = JsonLinesTracer::new(Vec::new());
+        for record in records.lock().unwrap().iter() {
+            tracer.trace(record);
+        }
+
+        assert_eq!(
+            String::from_utf8(tracer.into_inner()).unwrap(),
+            concat!(
+                r#"{"step":0,"instr_ptr":0,"opcode":"ARB","parameters":[{"value":3,"mode":"immediate"}],"operands":[3],"write":null}"#,
+                "\n",
+                r#"{"step":1,"instr_ptr":2,"opcode":"OUT","parameters":[{"value":-1,"mode":"relative"}],"operands":[204],"write":null}"#,
+                "\n",
+                r#"{"step":2,"instr_ptr":4,"opcode":"HALT","parameters":[],"operands":[],"write":null}"#,
+                "\n"
+            )
+        );
+
+        // Writing stops at the first error, such as a closed pipe, instead of panicking.
+        let mut buffer = [0; 8];
+        let mut tracer = JsonLinesTracer::new(&mut buffer[..]);
+        for record in records.lock().unwrap().iter() {
+            tracer.trace(record);
+        }
+        assert_eq!(
+            tracer.error().map(io::Error::kind),
+            Some(io::ErrorKind::WriteZero)
+        );
+    }
+}
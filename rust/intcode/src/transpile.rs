@@ -0,0 +1,587 @@
+//! Translates an Intcode program into a standalone Rust program.
+//!
+//! The generated `run` function loops over a `match` on the instruction pointer with one arm
+//! per basic block, so straight-line code runs without decoding anything. Blocks are found
+//! with a linear sweep of the whole image rather than by following jumps from address 0, since
+//! programs like the day 5 input only become valid once they've patched themselves.
+//!
+//! A small interpreter is generated too. It runs any instruction that isn't at the start of a
+//! block, and every block whose words the program has since overwritten. A write that changes
+//! a block's code marks it as overwritten, and if that write came from compiled code, execution
+//! carries on in the interpreter from the next instruction.
+//!
+//! The generated `main` takes the program's inputs as arguments and prints each output on its
+//! own line, so `rustc -O` is all it needs.
+
+use crate::{Instruction, OpCode, Parameter, ParameterMode};
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Write;
+
+/// How many program words go on each line of the generated `PROGRAM` array.
+const WORDS_PER_LINE: usize = 16;
+
+/// The parts of the generated program that don't depend on the image.
+const RUNTIME: &str = r#"
+fn at(a: i64) -> Result<usize, String> {
+    if a < 0 {
+        return Err(format!("negative address {}", a));
+    }
+    Ok(a as usize)
+}
+
+/// The result of checked arithmetic done by the instruction at `ip`.
+fn ck(v: Option<i64>, ip: usize) -> Result<i64, String> {
+    v.ok_or_else(|| format!("arithmetic overflow at {}", ip))
+}
+
+fn rd(m: &[i64], a: usize) -> i64 {
+    m.get(a).copied().unwrap_or(0)
+}
+
+/// Writes to an address that no block covers.
+fn wr(m: &mut Vec<i64>, a: usize, v: i64) {
+    if a >= m.len() {
+        m.resize(a + 1, 0);
+    }
+    m[a] = v;
+}
+
+/// Writes to an address that may hold code. Returns whether a block that was still compiled has
+/// been overwritten.
+fn st(m: &mut Vec<i64>, dirty: &mut [bool], a: usize, v: i64) -> bool {
+    if a >= m.len() {
+        m.resize(a + 1, 0);
+    }
+    let changed = m[a] != v;
+    m[a] = v;
+    changed && invalidate(dirty, a)
+}
+
+fn mode(m: &[i64], ip: usize, n: usize) -> i64 {
+    rd(m, ip) / [100, 1000, 10000][n - 1] % 10
+}
+
+fn addr(m: &[i64], ip: usize, rb: i64, n: usize) -> Result<usize, String> {
+    let p = rd(m, ip + n);
+    match mode(m, ip, n) {
+        0 => at(p),
+        2 => at(ck(rb.checked_add(p), ip)?),
+        1 => Err(format!("immediate parameter used as an address at {}", ip)),
+        mode => Err(format!("unknown parameter mode {} at {}", mode, ip)),
+    }
+}
+
+fn val(m: &[i64], ip: usize, rb: i64, n: usize) -> Result<i64, String> {
+    if mode(m, ip, n) == 1 {
+        return Ok(rd(m, ip + n));
+    }
+    addr(m, ip, rb, n).map(|a| rd(m, a))
+}
+
+/// Interprets the instruction at `ip`. Returns `false` if it halts.
+fn step(
+    m: &mut Vec<i64>,
+    dirty: &mut [bool],
+    ip: &mut usize,
+    rb: &mut i64,
+    input: &mut impl Iterator<Item = i64>,
+    output: &mut impl FnMut(i64),
+) -> Result<bool, String> {
+    let (i, r) = (*ip, *rb);
+    let (next, write) = match rd(m, i) % 100 {
+        1 => {
+            let v = ck(val(m, i, r, 1)?.checked_add(val(m, i, r, 2)?), i)?;
+            (i + 4, Some((addr(m, i, r, 3)?, v)))
+        }
+        2 => {
+            let v = ck(val(m, i, r, 1)?.checked_mul(val(m, i, r, 2)?), i)?;
+            (i + 4, Some((addr(m, i, r, 3)?, v)))
+        }
+        3 => {
+            let a = addr(m, i, r, 1)?;
+            (i + 2, Some((a, input.next().ok_or("ran out of input")?)))
+        }
+        4 => {
+            output(val(m, i, r, 1)?);
+            (i + 2, None)
+        }
+        5 if val(m, i, r, 1)? != 0 => (at(val(m, i, r, 2)?)?, None),
+        6 if val(m, i, r, 1)? == 0 => (at(val(m, i, r, 2)?)?, None),
+        5 | 6 => (i + 3, None),
+        7 => (i + 4, Some((addr(m, i, r, 3)?, (val(m, i, r, 1)? < val(m, i, r, 2)?) as i64))),
+        8 => (i + 4, Some((addr(m, i, r, 3)?, (val(m, i, r, 1)? == val(m, i, r, 2)?) as i64))),
+        9 => {
+            *rb = ck(r.checked_add(val(m, i, r, 1)?), i)?;
+            (i + 2, None)
+        }
+        99 => return Ok(false),
+        op => return Err(format!("unknown opcode {} at {}", op, i)),
+    };
+
+    if let Some((a, v)) = write {
+        st(m, dirty, a, v);
+    }
+    *ip = next;
+    Ok(true)
+}
+
+fn main() {
+    let inputs: Vec<i64> = std::env::args()
+        .skip(1)
+        .map(|a| a.parse().expect("Inputs must be integers"))
+        .collect();
+
+    if let Err(e) = run(&mut inputs.into_iter(), &mut |value| println!("{}", value)) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+"#;
+
+/// A straight run of instructions that ends in a jump or `Halt`, before another block starts,
+/// or before a word that doesn't decode.
+struct Block {
+    start: usize,
+    instructions: Vec<(usize, Instruction)>,
+    /// The address after the last instruction.
+    end: usize,
+}
+
+fn find_blocks(memory: &[i64]) -> Vec<Block> {
+    let decode = |address| {
+        Instruction::decode(memory, address)
+            .ok()
+            .filter(|instr| address + instr.opcode.instr_len() <= memory.len())
+    };
+
+    let mut leaders = BTreeSet::new();
+    leaders.insert(0);
+    let mut address = 0;
+    while address < memory.len() {
+        let instr = match decode(address) {
+            Some(instr) => instr,
+            None => {
+                address += 1;
+                leaders.insert(address);
+                continue;
+            }
+        };
+        address += instr.opcode.instr_len();
+
+        match instr.opcode {
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+                leaders.insert(address);
+                let target = instr.parameters()[1];
+                if target.mode == ParameterMode::Immediate && target.value >= 0 {
+                    leaders.insert(target.value as usize);
+                }
+            }
+            OpCode::Halt => {
+                leaders.insert(address);
+            }
+            _ => {}
+        }
+    }
+
+    leaders
+        .iter()
+        .filter_map(|&start| {
+            let mut instructions = Vec::new();
+            let mut address = start;
+            while let Some(instr) = decode(address) {
+                instructions.push((address, instr));
+                address += instr.opcode.instr_len();
+
+                let ends_block = match instr.opcode {
+                    OpCode::JumpIfTrue | OpCode::JumpIfFalse | OpCode::Halt => true,
+                    _ => leaders.contains(&address),
+                };
+                if ends_block {
+                    break;
+                }
+            }
+
+            if instructions.is_empty() {
+                None
+            } else {
+                Some(Block {
+                    start,
+                    instructions,
+                    end: address,
+                })
+            }
+        })
+        .collect()
+}
+
+/// A literal that can go anywhere in an expression.
+fn literal(value: i64) -> String {
+    if value < 0 {
+        format!("({})", value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// The address `parameter` of the instruction at `address` refers to.
+fn address_expr(parameter: Parameter, address: usize) -> String {
+    match parameter.mode {
+        ParameterMode::Position if parameter.value >= 0 => parameter.value.to_string(),
+        ParameterMode::Position => format!("at({})?", parameter.value),
+        _ => format!(
+            "at(ck(rb.checked_add({}), {})?)?",
+            literal(parameter.value),
+            address
+        ),
+    }
+}
+
+fn value_expr(parameter: Parameter, address: usize) -> String {
+    match parameter.mode {
+        ParameterMode::Immediate => literal(parameter.value),
+        _ => format!("rd(&m, {})", address_expr(parameter, address)),
+    }
+}
+
+/// Code for the instruction at `address` that ends with `next`.
+fn compile_instruction(
+    out: &mut String,
+    address: usize,
+    instr: &Instruction,
+    next: usize,
+    code: &HashSet<usize>,
+) {
+    let p = instr.parameters();
+    let value_expr = |parameter| value_expr(parameter, address);
+    writeln!(out, "                // {}: {}", address, instr).unwrap();
+
+    let value = match instr.opcode {
+        OpCode::Add => Some(format!(
+            "ck(i64::checked_add({}, {}), {})?",
+            value_expr(p[0]),
+            value_expr(p[1]),
+            address
+        )),
+        OpCode::Multiply => Some(format!(
+            "ck(i64::checked_mul({}, {}), {})?",
+            value_expr(p[0]),
+            value_expr(p[1]),
+            address
+        )),
+        OpCode::Input => Some("input.next().ok_or(\"ran out of input\")?".to_string()),
+        OpCode::LessThan => Some(format!(
+            "({} < {}) as i64",
+            value_expr(p[0]),
+            value_expr(p[1])
+        )),
+        OpCode::Equals => Some(format!(
+            "({} == {}) as i64",
+            value_expr(p[0]),
+            value_expr(p[1])
+        )),
+        OpCode::Output => {
+            writeln!(out, "                output({});", value_expr(p[0])).unwrap();
+            None
+        }
+        OpCode::JumpIfTrue | OpCode::JumpIfFalse => {
+            let comparison = if instr.opcode == OpCode::JumpIfTrue {
+                "!="
+            } else {
+                "=="
+            };
+            let target = match p[1].mode {
+                ParameterMode::Immediate if p[1].value >= 0 => p[1].value.to_string(),
+                _ => format!("at({})?", value_expr(p[1])),
+            };
+            writeln!(
+                out,
+                "                if {} {} 0 {{\n                    ip = {};\n                    continue;\n                }}",
+                value_expr(p[0]),
+                comparison,
+                target
+            )
+            .unwrap();
+            None
+        }
+        OpCode::AdjustRelativeBase => {
+            writeln!(
+                out,
+                "                rb = ck(rb.checked_add({}), {})?;",
+                value_expr(p[0]),
+                address
+            )
+            .unwrap();
+            None
+        }
+        OpCode::Halt => {
+            writeln!(out, "                return Ok(());").unwrap();
+            None
+        }
+    };
+
+    if let Some(value) = value {
+        let target = p[instr.opcode.output_param().unwrap()];
+        writeln!(out, "                let v = {};", value).unwrap();
+        match target.mode {
+            ParameterMode::Immediate => writeln!(
+                out,
+                "                return Err(\"immediate parameter used as an address at {}\".to_string());",
+                address
+            )
+            .unwrap(),
+            ParameterMode::Position
+                if target.value >= 0 && !code.contains(&(target.value as usize)) =>
+            {
+                writeln!(out, "                wr(&mut m, {}, v);", target.value).unwrap()
+            }
+            _ => writeln!(
+                out,
+                "                if st(&mut m, &mut dirty, {}, v) {{\n                    ip = {};\n                    continue;\n                }}",
+                address_expr(target, address),
+                next
+            )
+            .unwrap(),
+        }
+    }
+}
+
+/// Generates the source of a standalone Rust program that runs `memory` the same way
+/// `Program::run` would, taking its inputs as command line arguments.
+pub fn transpile(memory: &[i64]) -> String {
+    let blocks = find_blocks(memory);
+    let code: HashSet<usize> = blocks
+        .iter()
+        .flat_map(|block| block.start..block.end)
+        .collect();
+
+    let mut out = String::from(
+        "// Generated by the intcode transpiler.\n\
+         #![allow(unused)]\n\n",
+    );
+
+    writeln!(out, "const PROGRAM: [i64; {}] = [", memory.len()).unwrap();
+    for words in memory.chunks(WORDS_PER_LINE) {
+        let words: Vec<String> = words.iter().map(|w| w.to_string()).collect();
+        writeln!(out, "    {},", words.join(", ")).unwrap();
+    }
+    out.push_str("];\n\n");
+
+    // Words covered by the same blocks are grouped into ranges, so a write only has to find
+    // one arm.
+    out.push_str(
+        "/// Marks the blocks covering address `a` as overwritten. Returns whether any of them\n\
+         /// were still compiled.\n\
+         fn invalidate(dirty: &mut [bool], a: usize) -> bool {\n    \
+         let blocks: &[usize] = match a {\n",
+    );
+    let covering = |address: usize| -> Vec<usize> {
+        (0..blocks.len())
+            .filter(|&i| (blocks[i].start..blocks[i].end).contains(&address))
+            .collect()
+    };
+    let mut address = 0;
+    while address < memory.len() {
+        let indices = covering(address);
+        let mut end = address + 1;
+        while end < memory.len() && covering(end) == indices {
+            end += 1;
+        }
+        if !indices.is_empty() {
+            let indices: Vec<String> = indices.iter().map(|i| i.to_string()).collect();
+            writeln!(
+                out,
+                "        {}..={} => &[{}],",
+                address,
+                end - 1,
+                indices.join(", ")
+            )
+            .unwrap();
+        }
+        address = end;
+    }
+    out.push_str(
+        "        _ => &[],\n    \
+         };\n    \
+         let mut hit = false;\n    \
+         for &b in blocks {\n        \
+         hit |= !dirty[b];\n        \
+         dirty[b] = true;\n    \
+         }\n    \
+         hit\n\
+         }\n\n",
+    );
+
+    writeln!(
+        out,
+        "pub fn run(\n    \
+         input: &mut impl Iterator<Item = i64>,\n    \
+         output: &mut impl FnMut(i64),\n\
+         ) -> Result<(), String> {{\n    \
+         let mut m = PROGRAM.to_vec();\n    \
+         let mut dirty = [false; {}];\n    \
+         let (mut ip, mut rb) = (0usize, 0i64);\n\n    \
+         loop {{\n        \
+         match ip {{",
+        blocks.len()
+    )
+    .unwrap();
+
+    for (i, block) in blocks.iter().enumerate() {
+        writeln!(out, "            {} if !dirty[{}] => {{", block.start, i).unwrap();
+        for &(address, ref instr) in &block.instructions {
+            compile_instruction(
+                &mut out,
+                address,
+                instr,
+                address + instr.opcode.instr_len(),
+                &code,
+            );
+        }
+        if block.instructions.last().unwrap().1.opcode != OpCode::Halt {
+            writeln!(out, "                ip = {};", block.end).unwrap();
+        }
+        out.push_str("            }\n");
+    }
+
+    out.push_str(
+        "            _ => {\n                \
+         if !step(&mut m, &mut dirty, &mut ip, &mut rb, input, output)? {\n                    \
+         return Ok(());\n                \
+         }\n            \
+         }\n        \
+         }\n    \
+         }\n\
+         }\n",
+    );
+    out.push_str(RUNTIME);
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{assemble, IntcodeError, Program, Queue};
+    use std::convert::TryFrom;
+    use std::path::{Path, PathBuf};
+    use std::process::{self, Command};
+    use std::{env, fs};
+
+    fn outputs(memory: &[i64], input: i64) -> Vec<i64> {
+        let output = Queue::new();
+        let mut program = Program::new(memory.to_vec())
+            .with_input(Queue::from(vec![input]))
+            .with_output(output.clone());
+        program.run().unwrap();
+        output.drain()
+    }
+
+    /// Transpiles `memory` and compiles the result in a fresh directory. Returns the directory
+    /// and the executable.
+    fn compile(memory: &[i64], name: &str) -> (PathBuf, PathBuf) {
+        let dir = env::temp_dir().join(format!("intcode_transpile_{}_{}", name, process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join(format!("{}.rs", name));
+        let executable = dir.join(name);
+        fs::write(&source, transpile(memory)).unwrap();
+
+        let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+        let status = Command::new(rustc)
+            .args(["--edition", "2018", "-O", "-o"])
+            .arg(&executable)
+            .arg(&source)
+            .status()
+            .unwrap();
+        assert!(status.success(), "generated code doesn't compile");
+
+        (dir, executable)
+    }
+
+    #[test]
+    fn blocks() {
+        // The JT at 4 jumps into the middle of the ADD at 0.
+        let blocks = find_blocks(&[1101, 1, 1, 1, 1105, 1, 2, 99, 5]);
+        let spans: Vec<_> = blocks
+            .iter()
+            .map(|block| (block.start, block.instructions.len(), block.end))
+            .collect();
+
+        assert_eq!(spans, vec![(0, 2, 7), (2, 1, 6), (7, 1, 8)]);
+    }
+
+    #[test]
+    fn matches_interpreter_on_day_05() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../day_05/input.txt");
+        let memory = Program::try_from(fs::read_to_string(path).unwrap().as_str())
+            .unwrap()
+            .memory()
+            .to_vec();
+        let (dir, executable) = compile(&memory, "day_05");
+
+        for &input in &[1, 5] {
+            let run = Command::new(&executable)
+                .arg(input.to_string())
+                .output()
+                .unwrap();
+            assert!(run.status.success());
+
+            let compiled: Vec<i64> = String::from_utf8(run.stdout)
+                .unwrap()
+                .lines()
+                .map(|line| line.parse().unwrap())
+                .collect();
+            assert_eq!(compiled, outputs(&memory, input));
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reports_overflow() {
+        // Overflows an ADD, a MUL, a relative address and the relative base for inputs 1 to 4.
+        let source = "
+                    IN -> [x]
+                    ADD [x], #-1 -> [y]
+                    JF [y], #add
+                    ADD [y], #-1 -> [y]
+                    JF [y], #mul
+                    ARB [max]
+                    ADD [y], #-1 -> [y]
+                    JF [y], #address
+                    ARB [max]
+                    HALT
+            address: OUT [rb+1]
+                    HALT
+            add:    ADD [max], [x] -> [y]
+                    HALT
+            mul:    MUL [max], [x] -> [y]
+                    HALT
+            max:    data 9223372036854775807
+            x:      data 0
+            y:      data 0
+        ";
+        let memory = assemble(source).unwrap();
+        let (dir, executable) = compile(&memory, "overflow");
+
+        for input in 1..=4 {
+            let mut program = Program::new(memory.clone())
+                .with_input(Queue::from(vec![input]))
+                .with_output(Queue::new());
+            let instr_ptr = match program.run() {
+                Err(IntcodeError::Overflow { instr_ptr, .. }) => instr_ptr,
+                result => panic!("expected an overflow, got {:?}", result),
+            };
+
+            let run = Command::new(&executable)
+                .arg(input.to_string())
+                .output()
+                .unwrap();
+            assert!(!run.status.success());
+            assert_eq!(
+                String::from_utf8(run.stderr).unwrap(),
+                format!("error: arithmetic overflow at {}\n", instr_ptr)
+            );
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}